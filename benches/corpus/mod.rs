@@ -0,0 +1,192 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A generator of synthetic sources and source trees for the benchmarks. Everything is generated
+//! from a fixed seed, so that the benchmarks always run on the same input.
+
+// Not every benchmark uses every generator.
+#![allow(dead_code)]
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rlocc::locc::{languages, Language};
+
+/// The seed that all benchmarks generate their input from.
+pub const SEED: u64 = 0x726c_6f63_6321;
+
+/// A xorshift64* pseudo-random number generator; good enough to pick lines and file sizes with,
+/// and the same on every platform.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from the given seed (e.g., `SEED`); the same seed always produces the
+    /// same numbers.
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Rng(seed | 1)
+    }
+
+    /// Returns the next number, uniformly distributed over all of `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns a number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns one of the given items, or `None` if there are none.
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len())])
+        }
+    }
+}
+
+/// Returns the built-in languages whose files can be told by their name.
+pub fn named_languages() -> Vec<&'static Language> {
+    languages()
+        .filter(|lang| !lang.extensions.is_empty() || !lang.file_names.is_empty())
+        .collect()
+}
+
+/// Returns the given language by name.
+pub fn language(name: &str) -> &'static Language {
+    languages().find(|lang| lang.name == name).unwrap()
+}
+
+/// Generates a source of (at least) `size` bytes in the given language, made of code, comments of
+/// all kinds the language supports (both whole-line and trailing ones, as well as comments spanning
+/// multiple lines), string literals hiding comment tokens and blank lines.
+pub fn source(lang: &Language, rng: &mut Rng, size: usize) -> String {
+    let mut src = String::with_capacity(size + 256);
+    while src.len() < size {
+        let indent = "    ".repeat(rng.below(4));
+        let n = rng.below(1000);
+        let inline = rng.pick(lang.inline_comment_tokens);
+        let multiline = rng.below(lang.multiline_comment_start_tokens.len().max(1));
+        let multiline = lang
+            .multiline_comment_start_tokens
+            .get(multiline)
+            .map(|start| {
+                let end = lang
+                    .multiline_comment_end_tokens
+                    .get(multiline)
+                    .or_else(|| lang.multiline_comment_end_tokens.first())
+                    .unwrap_or(start);
+                (start, end)
+            });
+        let string = rng
+            .pick(lang.string_tokens)
+            .or_else(|| rng.pick(lang.raw_string_tokens));
+        let _ = match (rng.below(10), inline, multiline, string) {
+            (4, _, _, _) => writeln!(src),
+            (5, Some(inline), _, _) => writeln!(src, "{}{} comment number {}", indent, inline, n),
+            (6, Some(inline), _, _) => {
+                writeln!(src, "{}v{} = f(v{}); {} trailing", indent, n, n / 2, inline)
+            }
+            (7, _, Some((start, end)), _) => {
+                let _ = writeln!(src, "{}{} a comment", indent, start);
+                for i in 0..rng.below(4) {
+                    let _ = writeln!(src, "{}   spanning line {}", indent, i);
+                }
+                writeln!(src, "{}{}", indent, end)
+            }
+            (8, inline, multiline, Some(string)) => {
+                let token = inline.or_else(|| multiline.map(|(start, _)| start));
+                writeln!(
+                    src,
+                    "{}s{} = {}{} not a comment{}",
+                    indent,
+                    n,
+                    string,
+                    token.unwrap_or(&""),
+                    string
+                )
+            }
+            (9, _, Some((start, end)), _) => {
+                writeln!(src, "{}v{} = 1; {} c {} v{} = 2;", indent, n, start, end, n)
+            }
+            _ => writeln!(src, "{}v{} = f(v{}, {});", indent, n, n / 3, n % 7),
+        };
+    }
+    src
+}
+
+/// Generates a source tree of `num_files` files under `root`, in all named languages, nested up to
+/// three directories deep and of sizes from 64 bytes up to 256 KiB (i.e., both smaller and larger
+/// than the buffers the files are read with). Returns the total size of the files generated.
+pub fn tree(root: &Path, num_files: usize, rng: &mut Rng) -> usize {
+    let langs = named_languages();
+    let mut total = 0;
+    for i in 0..num_files {
+        let mut dir = root.to_owned();
+        for _ in 0..rng.below(4) {
+            dir.push(format!("d{}", rng.below(8)));
+        }
+        let lang = *rng.pick(&langs).unwrap();
+        let path = match rng.pick(lang.extensions) {
+            Some(ext) => dir.join(format!("f{}.{}", i, ext)),
+            None => dir
+                .join(format!("n{}", i))
+                .join(rng.pick(lang.file_names).unwrap()),
+        };
+        let size = 64 << rng.below(13);
+        let src = source(lang, rng, size);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &src).unwrap();
+        total += src.len();
+    }
+    total
+}
+
+/// Generates `num_paths` paths of files: mostly ones whose language is told by their extension,
+/// but also by their exact name or a pattern of it, as well as ones of no known language at all.
+pub fn paths(num_paths: usize, rng: &mut Rng) -> Vec<PathBuf> {
+    let langs = named_languages();
+    (0..num_paths)
+        .map(|i| {
+            let lang = rng.pick(&langs).unwrap();
+            let name = match rng.below(10) {
+                0 => lang.file_names.first().map(|name| name.to_string()),
+                1 => lang
+                    .file_name_patterns
+                    .first()
+                    .map(|pattern| pattern.replace('*', "x")),
+                2 => Some(format!("f{}.unknown", i)),
+                3 => Some(format!("F{}", i)),
+                _ => None,
+            };
+            let name = name.unwrap_or_else(|| match rng.pick(lang.extensions) {
+                Some(ext) => format!("f{}.{}", i, ext),
+                None => lang.file_names[0].to_owned(),
+            });
+            Path::new("src")
+                .join(format!("d{}", rng.below(8)))
+                .join(name)
+        })
+        .collect()
+}
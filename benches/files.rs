@@ -0,0 +1,85 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use rlocc::locc::{count_all, Config};
+
+mod corpus;
+
+/// The number of files counted in each iteration, and the size of each of them.
+const NUM_FILES: usize = 16;
+const FILE_SIZE: usize = 1 << 20;
+
+/// Compares memory-mapping files to reading them line by line (i.e., `--no-mmap`), counting the
+/// same large files with a single Worker.
+fn read(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut rng = corpus::Rng::new(corpus::SEED);
+    let lang = corpus::language("Rust");
+    let mut bytes = 0;
+    for i in 0..NUM_FILES {
+        let source = corpus::source(lang, &mut rng, FILE_SIZE);
+        fs::write(dir.path().join(format!("bench{}.rs", i)), &source).unwrap();
+        bytes += source.len();
+    }
+
+    let mut group = c.benchmark_group("count_all");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    for (name, no_mmap) in [("mmap", false), ("no-mmap", true)] {
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 1,
+            no_mmap,
+            ..Default::default()
+        };
+        group.bench_function(name, |b| b.iter(|| count_all(&config).unwrap()));
+    }
+    group.finish();
+}
+
+/// The number of files in the synthetic source tree counted in each iteration.
+const TREE_FILES: usize = 1000;
+
+/// Counts a synthetic source tree in all languages, with a single Worker and with as many as the
+/// default configuration uses.
+fn tree(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut rng = corpus::Rng::new(corpus::SEED);
+    let bytes = corpus::tree(dir.path(), TREE_FILES, &mut rng);
+
+    let mut group = c.benchmark_group("count_all/tree");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    let mut threads = vec![1, Config::default().num_threads];
+    threads.dedup();
+    for num_threads in threads {
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads,
+            ..Default::default()
+        };
+        group.bench_function(format!("{} threads", num_threads), |b| {
+            b.iter(|| count_all(&config).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, read, tree);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use rlocc::locc::guess_language;
+
+mod corpus;
+
+/// The number of paths guessed the language of in each iteration.
+const NUM_PATHS: usize = 10_000;
+
+fn guess(c: &mut Criterion) {
+    let mut rng = corpus::Rng::new(corpus::SEED);
+    let paths = corpus::paths(NUM_PATHS, &mut rng);
+
+    let mut group = c.benchmark_group("guess_language");
+    group.throughput(Throughput::Elements(paths.len() as u64));
+    group.bench_function("paths", |b| {
+        b.iter(|| {
+            for path in paths.iter() {
+                let _ = black_box(guess_language(black_box(path)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, guess);
+criterion_main!(benches);
@@ -0,0 +1,160 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::path::Path;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use rlocc::locc::{guess_language, CountResult, LOCStateMachine, Language, ParsingState};
+
+mod corpus;
+
+/// The lines that the source of each language benchmarked is made of, repeated over and over:
+/// code, comments of both kinds, string literals hiding comment tokens and blank lines.
+static SOURCES: &[(&str, &[&str])] = &[
+    (
+        "bench.rs",
+        &[
+            "/// Documentation of the function below.",
+            "fn main() {",
+            "    let s = \"// not a comment\"; // a comment",
+            "    /* a comment */ let x = 42;",
+            "",
+            "    /* a comment",
+            "       spanning /* nested */ lines */",
+            "    println!(\"{} {}\", s, x);",
+            "}",
+        ],
+    ),
+    (
+        "bench.c",
+        &[
+            "#include <stdio.h>",
+            "",
+            "/*",
+            " * A block comment.",
+            " */",
+            "int main(void) {",
+            "    char *s = \"/* \\\" */\"; // a comment",
+            "    return 0;",
+            "}",
+        ],
+    ),
+    (
+        "bench.py",
+        &[
+            "\"\"\"",
+            "A docstring.",
+            "\"\"\"",
+            "",
+            "# A comment.",
+            "def main():",
+            "    s = '# not a comment'  # a comment",
+            "    return s",
+        ],
+    ),
+    (
+        "bench.lua",
+        &[
+            "--[[",
+            "  A block comment.",
+            "]]",
+            "local s = \"-- not a comment\" -- a comment",
+            "",
+            "--[==[ a comment ]==] print(s)",
+        ],
+    ),
+    (
+        "bench.html",
+        &[
+            "<!DOCTYPE html>",
+            "<html>",
+            "  <!-- a comment -->",
+            "  <body class=\"main\">",
+            "",
+            "    <p>Some <b>text</b>.</p> <!-- a",
+            "    comment -->",
+            "  </body>",
+            "</html>",
+        ],
+    ),
+];
+
+/// The number of lines fed to the state machine in each iteration.
+const NUM_LINES: usize = 10_000;
+
+/// Feeds the given lines to the state machine, the same way each Worker does.
+fn count(sm: &mut LOCStateMachine, lang: &Language, lines: &[&str]) -> CountResult {
+    let mut cr = CountResult::new(lang.name);
+    sm.reset();
+    for line in lines {
+        let mut ps = ParsingState::new(lang);
+        ps.curr_line = Some(line.trim_start());
+        sm.process(&mut ps, &mut cr);
+        cr.total += 1;
+    }
+    cr
+}
+
+fn process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LOCStateMachine::process");
+    for (path, source) in SOURCES {
+        let (_, lang) = guess_language(&Path::new(path)).unwrap();
+        let lines: Vec<&str> = source.iter().cycle().take(NUM_LINES).copied().collect();
+        let bytes: usize = lines.iter().map(|line| line.len() + 1).sum();
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_function(lang.name, |b| {
+            let mut sm = LOCStateMachine::new();
+            b.iter(|| count(&mut sm, lang, black_box(&lines)))
+        });
+    }
+    group.finish();
+}
+
+/// The size of the synthetic source of each language fed to the state machine in each iteration.
+const SYNTHETIC_SIZE: usize = 1 << 14;
+
+fn process_synthetic(c: &mut Criterion) {
+    let mut rng = corpus::Rng::new(corpus::SEED);
+    let sources: Vec<(&Language, String)> = corpus::named_languages()
+        .into_iter()
+        .map(|lang| (lang, corpus::source(lang, &mut rng, SYNTHETIC_SIZE)))
+        .collect();
+    let sources: Vec<(&Language, Vec<&str>)> = sources
+        .iter()
+        .map(|(lang, source)| (*lang, source.lines().collect()))
+        .collect();
+    let bytes: usize = sources
+        .iter()
+        .flat_map(|(_, lines)| lines.iter().map(|line| line.len() + 1))
+        .sum();
+
+    let mut group = c.benchmark_group("LOCStateMachine::process");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("all languages", |b| {
+        let mut sm = LOCStateMachine::new();
+        b.iter(|| {
+            for (lang, lines) in sources.iter() {
+                black_box(count(&mut sm, lang, black_box(lines)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, process, process_synthetic);
+criterion_main!(benches);
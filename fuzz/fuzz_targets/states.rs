@@ -0,0 +1,51 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Feeds arbitrary bytes, as the content of a file in each language, through the state machine,
+//! making sure that it never panics and that each line is counted exactly once.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use rlocc::locc::{languages, CountResult, LOCStateMachine, ParsingState};
+
+fuzz_target!(|data: &[u8]| {
+    // Files are read the same way, with any invalid UTF-8 replaced.
+    let content = String::from_utf8_lossy(data);
+    let num_lines = content.split_inclusive('\n').count();
+
+    let mut sm = LOCStateMachine::new();
+    for lang in languages() {
+        let mut cr = CountResult::new(lang.name);
+        sm.reset();
+        for line in content.split_inclusive('\n') {
+            let mut ps = ParsingState::new(lang);
+            ps.curr_line = Some(line.trim_start());
+            sm.process(&mut ps, &mut cr);
+            cr.total += 1;
+        }
+        assert_eq!(cr.total, num_lines, "{}", lang.name);
+        assert_eq!(
+            cr.total,
+            cr.code + cr.comments + cr.blank,
+            "{}: {:?}",
+            lang.name,
+            cr
+        );
+    }
+});
@@ -15,39 +15,392 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::collections::HashSet;
 use std::env;
-use std::path::PathBuf;
+use std::error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::ignores::{self, Globs};
+use super::languages;
+#[cfg(feature = "serde")]
+use super::project::ProjectConfig;
+
+/// The number of files and directories that may be queued for the workers at once, by default.
+const DEFAULT_QUEUE_CAPACITY: usize = 4096;
+
+/// Expands to the given lines of the usage message if rlocc is built with the `serde` feature,
+/// or to the alternative ones (if any) otherwise.
+#[cfg(feature = "serde")]
+macro_rules! with_serde {
+    ($lines:literal $(, $without:literal)?) => {
+        $lines
+    };
+}
+#[cfg(not(feature = "serde"))]
+macro_rules! with_serde {
+    ($lines:literal) => {
+        ""
+    };
+    ($lines:literal, $without:literal) => {
+        $without
+    };
+}
+
+/// The usage message of the `rlocc` binary; options depending on features that rlocc has not been
+/// built with are left out.
+pub const USAGE: &str = concat!(
+    "\
+Usage: rlocc [OPTIONS] [PATH]...
+
+Count lines of code in the given files or directories (default: the current directory).
+
+Options:
+  -t, --threads <N>           Number of worker threads (default: number of CPUs)
+      --queue-capacity <N>    Number of files and directories queued for the worker threads
+                              (default: 4096, or the number of threads with --low-memory)
+      --low-memory            Keep queues and buffers small, e.g., for very large trees
+      --no-mmap               Read files line by line instead of mapping them in memory
+  -e, --exclude <GLOB>        Skip files and directories matching GLOB (repeatable)
+  -i, --include <GLOB>        Only count files matching GLOB (repeatable)
+  -l, --lang <LANGS>          Only count files in the given comma-separated languages
+  -x, --exclude-lang <LANGS>  Don't count files in the given comma-separated languages
+",
+    with_serde!(
+        "  -o, --output-format <FMT>   Output format: table, json (default: table)\n",
+        "  -o, --output-format <FMT>   Output format: table (default: table)\n"
+    ),
+    "  -s, --sort <KEY>            Sort by: language, files, lines, blanks, comments, code
+                              (default: code)
+      --by-file               Report results for each file separately
+      --no-ignore             Don't respect .gitignore, .ignore and git's exclude files
+  -L, --follow-symlinks       Follow symbolic links while walking directories
+      --dedup                 Count files reached through multiple paths (e.g., hard links) once
+      --dedup-content         Count files with identical contents once (implies --dedup)
+  -v, --verbose               List each file that has been skipped, and why
+",
+    with_serde!(
+        "      --lang-file <FILE>      Load additional languages from a TOML or JSON file (repeatable)
+      --no-config             Don't look for .rlocc.toml project configuration files
+"
+    ),
+    "      --strict                Exit with a non-zero status if any file could not be read
+  -h, --help                  Print this help message and exit
+  -V, --version               Print version information and exit"
+);
 
 /// TODO: Documentation
 #[derive(Debug)]
 pub struct Config {
     pub paths: Vec<PathBuf>,
     pub num_threads: usize,
+    /// The number of files and directories that may be queued for the workers at once; if
+    /// `None`, it depends on `low_memory`.
+    pub queue_capacity: Option<usize>,
+    /// Whether to keep memory usage low at the expense of speed.
+    pub low_memory: bool,
+    /// Whether to read files line by line, rather than map them in memory.
+    pub no_mmap: bool,
+
+    /// Gitignore-style glob patterns of files and directories to be skipped while walking the
+    /// given paths.
+    pub excludes: Vec<String>,
+    /// Gitignore-style glob patterns of the only files to be counted; empty means all of them.
+    pub includes: Vec<String>,
+    /// The glob patterns of the project configuration files found for the given paths.
+    pub project_globs: Vec<ProjectGlobs>,
+    /// Lowercase names (or aliases, until resolved) of the only languages to be counted; empty
+    /// means all of them.
+    pub include_langs: Vec<String>,
+    /// Lowercase names (or aliases, until resolved) of the languages not to be counted.
+    pub exclude_langs: Vec<String>,
+    pub output_format: OutputFormat,
+    pub sort: SortBy,
+    pub by_file: bool,
+    /// Whether to disregard `.gitignore`, `.ignore` and git's exclude files while walking.
+    pub no_ignore: bool,
+    /// Whether to follow symbolic links found while walking directories.
+    pub follow_symlinks: bool,
+    /// Whether to skip paths leading to files that have already been counted (e.g., hard links).
+    pub dedup: bool,
+    /// Whether to skip files identical to files of the same language that have been counted.
+    pub dedup_content: bool,
+    /// Whether to keep the path of each file that has been skipped.
+    pub verbose: bool,
+    /// Whether I/O errors while walking or counting should be treated as fatal.
+    pub strict: bool,
+    /// Files to load additional languages from, besides those in rlocc's configuration directory.
+    pub lang_files: Vec<PathBuf>,
+    /// Whether to disregard `.rlocc.toml` project configuration files.
+    pub no_config: bool,
+    /// Extensions mapped to the names of the languages of their files, overriding any guess.
+    pub extension_langs: HashMap<String, String>,
+    /// Lowercase names of the languages that count as documentation rather than code.
+    pub documentation_langs: Vec<String>,
 }
 
 /// TODO: Documentation
 impl Config {
-    /// TODO: Documentation
-    pub fn new<T>(args: T, num_threads: usize) -> Result<Self, &'static str>
+    /// Parses the given command line arguments (excluding the name of the program) into a new
+    /// `Config`. If no paths are given, the current working directory is used.
+    ///
+    /// Unless `--no-config` is given, the settings of any `.rlocc.toml` project configuration
+    /// files found in the paths or their ancestors are merged into the `Config`, with the command
+    /// line arguments taking precedence.
+    ///
+    /// `num_threads` is the number of threads to use unless overridden by `--threads`; zero
+    /// means the number of available CPUs.
+    pub fn new<T>(args: T, num_threads: usize) -> Result<Self, ConfigError>
     where
         T: Iterator<Item = String>,
     {
+        let mut ret = Config {
+            paths: Vec::new(),
+            num_threads,
+            ..Default::default()
+        };
+
+        let mut output_format = None;
         let mut args = args.peekable();
-        if args.peek().is_none() {
-            return Err("no paths given");
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                ret.paths.extend(args.by_ref().map(PathBuf::from));
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                ret.paths.push(PathBuf::from(arg));
+                continue;
+            }
+
+            // Support both "--flag value" and "--flag=value".
+            let (flag, mut value) = match arg.find('=') {
+                Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_owned())),
+                _ => (&arg[..], None),
+            };
+            let mut value = |flag: &str| {
+                value
+                    .take()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ConfigError::MissingValue(flag.to_owned()))
+            };
+            let glob = |flag: &str, v: String| {
+                if ignores::is_valid_glob(&v) {
+                    Ok(v)
+                } else {
+                    Err(ConfigError::InvalidValue(flag.to_owned(), v))
+                }
+            };
+
+            match flag {
+                "-h" | "--help" => return Err(ConfigError::Help),
+                "-V" | "--version" => return Err(ConfigError::Version),
+                "-t" | "--threads" => {
+                    let v = value(flag)?;
+                    ret.num_threads = match v.parse() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Err(ConfigError::InvalidValue(flag.to_owned(), v)),
+                    };
+                }
+                "--queue-capacity" => {
+                    let v = value(flag)?;
+                    ret.queue_capacity = match v.parse() {
+                        Ok(n) if n > 0 => Some(n),
+                        _ => return Err(ConfigError::InvalidValue(flag.to_owned(), v)),
+                    };
+                }
+                "--low-memory" => ret.low_memory = true,
+                "--no-mmap" => ret.no_mmap = true,
+                "-e" | "--exclude" => ret.excludes.push(glob(flag, value(flag)?)?),
+                "-i" | "--include" => ret.includes.push(glob(flag, value(flag)?)?),
+                "-l" | "--lang" | "--include-lang" => {
+                    ret.include_langs.extend(lang_list(&value(flag)?))
+                }
+                "-x" | "--exclude-lang" => ret.exclude_langs.extend(lang_list(&value(flag)?)),
+                "-o" | "--output-format" => {
+                    let v = value(flag)?;
+                    #[cfg(not(feature = "serde"))]
+                    if v.eq_ignore_ascii_case("json") {
+                        let arg = format!("{} {}", flag, v);
+                        return Err(ConfigError::MissingFeature(arg, "serde"));
+                    }
+                    output_format = Some(
+                        v.parse()
+                            .map_err(|_| ConfigError::InvalidValue(flag.to_owned(), v))?,
+                    );
+                }
+                "-s" | "--sort" => {
+                    let v = value(flag)?;
+                    ret.sort = v
+                        .parse()
+                        .map_err(|_| ConfigError::InvalidValue(flag.to_owned(), v))?;
+                }
+                "--by-file" => ret.by_file = true,
+                "--no-ignore" => ret.no_ignore = true,
+                "-L" | "--follow-symlinks" => ret.follow_symlinks = true,
+                "--dedup" => ret.dedup = true,
+                "--dedup-content" => {
+                    ret.dedup = true;
+                    ret.dedup_content = true;
+                }
+                "-v" | "--verbose" => ret.verbose = true,
+                "--strict" => ret.strict = true,
+                #[cfg(feature = "serde")]
+                "--lang-file" => ret.lang_files.push(PathBuf::from(value(flag)?)),
+                #[cfg(feature = "serde")]
+                "--no-config" => ret.no_config = true,
+                #[cfg(not(feature = "serde"))]
+                "--lang-file" | "--no-config" => {
+                    return Err(ConfigError::MissingFeature(flag.to_owned(), "serde"))
+                }
+                _ => return Err(ConfigError::UnknownFlag(arg)),
+            }
+        }
+
+        if ret.paths.is_empty() {
+            ret.paths.push(env::current_dir().unwrap());
+        }
+        if ret.num_threads == 0 {
+            ret.num_threads = num_cpus::get();
+        }
+
+        #[cfg(feature = "serde")]
+        if !ret.no_config {
+            let mut found = HashSet::new();
+            for path in ret.paths.clone() {
+                for (dir, project) in ProjectConfig::discover(&path)? {
+                    // Files shared by several of the paths are only merged once.
+                    if found.insert(dir.clone()) {
+                        ret.merge_project_config(dir, project);
+                    }
+                }
+            }
+        }
+        if let Some(output_format) = output_format {
+            ret.output_format = output_format;
         }
+        Ok(ret)
+    }
+
+    /// Merges the settings of the project configuration file in the given directory into the
+    /// `Config`, overriding those of any files merged before it (i.e., in its ancestors). Its
+    /// glob patterns are kept apart, since they are anchored to its directory.
+    #[cfg(feature = "serde")]
+    fn merge_project_config(&mut self, dir: PathBuf, project: ProjectConfig) {
+        if !project.exclude.is_empty() || !project.include.is_empty() {
+            self.project_globs.push(ProjectGlobs {
+                dir,
+                excludes: project.exclude,
+                includes: project.include,
+            });
+        }
+        for (ext, lang) in project.extensions {
+            self.extension_langs
+                .insert(ext.trim_start_matches('.').to_owned(), lang);
+        }
+        self.documentation_langs
+            .extend(project.documentation.iter().map(|lang| lang.to_lowercase()));
+        if let Some(format) = project.output_format.and_then(|f| f.parse().ok()) {
+            self.output_format = format;
+        }
+    }
+
+    /// Returns the exclude and include glob patterns compiled for the given path to be walked,
+    /// along with those of the project configuration files found in it or its ancestors.
+    #[inline]
+    pub fn globs(&self, root: &Path) -> Globs {
+        let mut globs = Globs::new(root, &self.excludes, &self.includes);
+        globs.add_projects(root, &self.project_globs);
+        globs
+    }
+
+    /// Returns the name of the language that files with the extension of the given path are
+    /// explicitly mapped to, if any.
+    #[inline]
+    pub fn extension_lang<P>(&self, path: &P) -> Option<&str>
+    where
+        P: AsRef<Path>,
+    {
+        if self.extension_langs.is_empty() {
+            return None;
+        }
+        let ext = path.as_ref().extension()?.to_str()?;
+        self.extension_langs.get(ext).map(String::as_str)
+    }
+
+    /// Returns whether the language with the given name counts as documentation rather than code.
+    #[inline]
+    pub fn is_documentation(&self, name: &str) -> bool {
+        self.documentation_langs
+            .iter()
+            .any(|lang| lang.eq_ignore_ascii_case(name))
+    }
 
-        Ok(Config {
-            paths: args.map(PathBuf::from).collect(),
-            num_threads: if num_threads > 0 {
-                num_threads
-            } else {
-                num_cpus::get()
-            },
-        })
+    /// Resolves the names and aliases (e.g., `cpp`, `js` or `ts`) of the languages to be
+    /// included or excluded to the (lowercase) names of the supported languages. Any
+    /// user-defined languages have to be registered beforehand.
+    pub fn resolve_langs(&mut self) -> Result<(), ConfigError> {
+        let resolve = |flag: &str, langs: &mut Vec<String>| {
+            for lang in langs.iter_mut() {
+                match languages::find_language(lang) {
+                    Some(found) => *lang = found.name.to_lowercase(),
+                    None => return Err(ConfigError::InvalidValue(flag.to_owned(), lang.clone())),
+                }
+            }
+            Ok(())
+        };
+        resolve("--lang", &mut self.include_langs)?;
+        resolve("--exclude-lang", &mut self.exclude_langs)
+    }
+
+    /// Returns whether files written in the language with the given name should be counted.
+    #[inline]
+    pub fn is_lang_included(&self, name: &str) -> bool {
+        (self.include_langs.is_empty()
+            || self
+                .include_langs
+                .iter()
+                .any(|lang| lang.eq_ignore_ascii_case(name)))
+            && !self
+                .exclude_langs
+                .iter()
+                .any(|lang| lang.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the number of files and directories that may be queued for the workers at once.
+    #[inline]
+    pub fn queue_capacity(&self) -> usize {
+        match self.queue_capacity {
+            Some(capacity) => capacity,
+            None if self.low_memory => self.num_threads,
+            None => DEFAULT_QUEUE_CAPACITY,
+        }
+    }
+
+    /// Returns whether the paths of the files skipped should be kept, to be listed along with the
+    /// results.
+    #[inline]
+    pub fn keeps_skipped_paths(&self) -> bool {
+        self.verbose || self.strict
+    }
+
+    /// Returns whether any languages have been included or excluded.
+    #[inline]
+    pub fn filters_langs(&self) -> bool {
+        !self.include_langs.is_empty() || !self.exclude_langs.is_empty()
     }
 }
 
+/// Splits a comma-separated list of languages into their lowercase names.
+fn lang_list(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',')
+        .map(str::trim)
+        .filter(|lang| !lang.is_empty())
+        .map(str::to_lowercase)
+}
+
 /// TODO: Documentation
 impl Default for Config {
     #[inline]
@@ -55,10 +408,130 @@ impl Default for Config {
         Config {
             paths: vec![env::current_dir().unwrap()],
             num_threads: num_cpus::get(),
+            queue_capacity: None,
+            low_memory: false,
+            no_mmap: false,
+            excludes: Vec::new(),
+            includes: Vec::new(),
+            project_globs: Vec::new(),
+            include_langs: Vec::new(),
+            exclude_langs: Vec::new(),
+            output_format: OutputFormat::default(),
+            sort: SortBy::default(),
+            by_file: false,
+            no_ignore: false,
+            follow_symlinks: false,
+            dedup: false,
+            dedup_content: false,
+            verbose: false,
+            strict: false,
+            lang_files: Vec::new(),
+            no_config: false,
+            extension_langs: HashMap::new(),
+            documentation_langs: Vec::new(),
+        }
+    }
+}
+
+/// The glob patterns of a project configuration file, anchored to the directory it is in rather
+/// than to the paths walked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectGlobs {
+    /// The canonical path of the directory of the file.
+    pub dir: PathBuf,
+    pub excludes: Vec<String>,
+    pub includes: Vec<String>,
+}
+
+/// The format in which the results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            #[cfg(feature = "serde")]
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The column by which the results are sorted. Numeric columns are sorted in descending order,
+/// while languages are sorted alphabetically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    Language,
+    Files,
+    Lines,
+    Blanks,
+    Comments,
+    #[default]
+    Code,
+}
+
+impl FromStr for SortBy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "language" | "lang" => Ok(SortBy::Language),
+            "files" => Ok(SortBy::Files),
+            "lines" => Ok(SortBy::Lines),
+            "blanks" | "blank" => Ok(SortBy::Blanks),
+            "comments" | "comment" => Ok(SortBy::Comments),
+            "code" => Ok(SortBy::Code),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The ways in which parsing the command line arguments may not result in a `Config`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The usage message has been requested.
+    Help,
+    /// The version information has been requested.
+    Version,
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+    /// The given argument requires a feature that rlocc has not been built with.
+    MissingFeature(String, &'static str),
+    /// A project configuration file could not be read or parsed.
+    ProjectConfig(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{}", USAGE),
+            ConfigError::Version => write!(f, "rlocc {}", env!("CARGO_PKG_VERSION")),
+            ConfigError::UnknownFlag(flag) => write!(f, "unknown flag '{}'", flag),
+            ConfigError::MissingValue(flag) => write!(f, "flag '{}' requires a value", flag),
+            ConfigError::InvalidValue(flag, value) => {
+                write!(f, "invalid value '{}' for flag '{}'", value, flag)
+            }
+            ConfigError::MissingFeature(arg, feature) => write!(
+                f,
+                "'{}' requires rlocc to be built with the '{}' feature",
+                arg, feature
+            ),
+            ConfigError::ProjectConfig(path, err) => write!(f, "{}: {}", path.display(), err),
         }
     }
 }
 
+impl error::Error for ConfigError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,8 +539,8 @@ mod tests {
 
     #[test]
     fn test_default() {
-        let args = vec!["skata", "re"];
-        let c = Config::new(args.iter().map(|s| s.to_string()), 2).unwrap_or_default();
+        let args = ["skata", "re"];
+        let c = Config::new(args_no_config(&args), 2).unwrap_or_default();
         eprintln!("config1: {:#?}", c);
         assert_eq!(
             c.paths,
@@ -77,10 +550,256 @@ mod tests {
         );
         assert_eq!(c.num_threads, 2);
 
-        let args: Vec<String> = vec![ /* no arguments given */ ];
-        let c = Config::new(args.iter().map(|s| s.to_string()), 0).unwrap_or_default();
+        let args: Vec<&str> = vec![ /* no arguments given */ ];
+        let c = Config::new(args_no_config(&args), 0).unwrap_or_default();
         eprintln!("config2: {:#?}", c);
         assert_eq!(c.paths, vec![env::current_dir().unwrap()]);
         assert_eq!(c.num_threads, num_cpus::get());
+        assert_eq!(c.queue_capacity(), DEFAULT_QUEUE_CAPACITY);
+
+        let args = ["-t", "3", "--low-memory"];
+        let c = Config::new(args_no_config(&args), 0).unwrap();
+        assert_eq!(c.queue_capacity(), 3);
+    }
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Like `args`, but also keeps any project configuration files around the current directory
+    /// from being read, in builds that read them, so that the results do not depend on where the
+    /// tests are run.
+    fn args_no_config(a: &[&str]) -> impl Iterator<Item = String> {
+        let no_config = cfg!(feature = "serde").then(|| "--no-config".to_owned());
+        no_config.into_iter().chain(args(a))
+    }
+
+    #[test]
+    fn test_flags() {
+        let c = Config::new(
+            args_no_config(&[
+                "-t",
+                "3",
+                "--exclude=target",
+                "p1",
+                "--exclude",
+                "node_modules",
+                "--include-lang",
+                "Rust,toml",
+                "--sort=lines",
+                "--output-format",
+                "table",
+                "--by-file",
+                "-i",
+                "*.rs",
+                "--no-ignore",
+                "-L",
+                "--queue-capacity",
+                "16",
+                "--low-memory",
+                "--no-mmap",
+                "--dedup-content",
+                "-v",
+                "--strict",
+                "--",
+                "--p2",
+            ]),
+            0,
+        )
+        .unwrap();
+        assert_eq!(c.paths, vec![PathBuf::from("p1"), PathBuf::from("--p2")]);
+        assert_eq!(c.num_threads, 3);
+        assert_eq!(c.excludes, vec!["target", "node_modules"]);
+        assert_eq!(c.includes, vec!["*.rs"]);
+        assert_eq!(c.include_langs, vec!["rust", "toml"]);
+        assert_eq!(c.output_format, OutputFormat::Table);
+        assert_eq!(c.sort, SortBy::Lines);
+        assert!(c.by_file);
+        assert!(c.no_ignore);
+        assert!(c.follow_symlinks);
+        assert!(c.dedup && c.dedup_content);
+        assert_eq!(c.queue_capacity(), 16);
+        assert!(c.low_memory);
+        assert!(c.no_mmap);
+        assert!(c.verbose);
+        assert!(c.strict);
+
+        let globs = c.globs(Path::new("a"));
+        assert!(globs.is_excluded(Path::new("a/b/target"), true));
+        assert!(!globs.is_excluded(Path::new("a/target/b"), false));
+        assert!(globs.is_included(Path::new("a/target/b.rs")));
+        assert!(!globs.is_included(Path::new("a/target/b.go")));
+        assert!(c.is_lang_included("Rust"));
+        assert!(c.is_lang_included("TOML"));
+        assert!(!c.is_lang_included("Go"));
+        assert!(c.filters_langs());
+    }
+
+    #[test]
+    fn test_lang_filters() {
+        let mut c = Config::new(
+            args_no_config(&["--lang", "CPP, js,ts", "--lang=rs", "-x", "markdown,json"]),
+            1,
+        )
+        .unwrap();
+        assert_eq!(c.include_langs, vec!["cpp", "js", "ts", "rs"]);
+        assert_eq!(c.exclude_langs, vec!["markdown", "json"]);
+        c.resolve_langs().unwrap();
+        assert_eq!(
+            c.include_langs,
+            vec!["c++", "javascript", "typescript", "rust"]
+        );
+        assert!(c.is_lang_included("C++"));
+        assert!(c.is_lang_included("TypeScript"));
+        assert!(!c.is_lang_included("Markdown"));
+        assert!(!c.is_lang_included("Go"));
+
+        let mut c = Config::new(args_no_config(&["-x", "json"]), 1).unwrap();
+        c.resolve_langs().unwrap();
+        assert!(c.is_lang_included("Go"));
+        assert!(!c.is_lang_included("JSON"));
+        assert!(!Config::new(args_no_config(&[]), 1).unwrap().filters_langs());
+
+        let mut c = Config::new(args_no_config(&["--exclude-lang", "klingon"]), 1).unwrap();
+        assert_eq!(
+            c.resolve_langs().unwrap_err(),
+            ConfigError::InvalidValue("--exclude-lang".to_owned(), "klingon".to_owned())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lang_file_flag() {
+        let c = Config::new(
+            args_no_config(&["--lang-file", "a.toml", "--lang-file=b.json"]),
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            c.lang_files,
+            vec![PathBuf::from("a.toml"), PathBuf::from("b.json")]
+        );
+        assert_eq!(
+            Config::new(args(&["--lang-file"]), 1).unwrap_err(),
+            ConfigError::MissingValue("--lang-file".to_owned())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_project_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".rlocc.toml"),
+            "exclude = [\"vendor\"]\ndocumentation = [\"Markdown\"]\noutput_format = \"json\"\n\n\
+             [extensions]\n\".inc\" = \"PHP\"\n",
+        )
+        .unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        let c = Config::new(args(&["-e", "target", path]), 1).unwrap();
+        assert_eq!(c.excludes, vec!["target"]);
+        assert_eq!(
+            c.project_globs,
+            vec![ProjectGlobs {
+                dir: std::fs::canonicalize(dir.path()).unwrap(),
+                excludes: vec!["vendor".to_owned()],
+                includes: Vec::new(),
+            }]
+        );
+        assert_eq!(c.output_format, OutputFormat::Json);
+        assert_eq!(c.extension_lang(&"a/b.inc"), Some("PHP"));
+        assert_eq!(c.extension_lang(&"a/b.rs"), None);
+        assert!(c.is_documentation("Markdown"));
+        assert!(!c.is_documentation("Rust"));
+
+        let c = Config::new(args(&["-o", "table", path]), 1).unwrap();
+        assert_eq!(c.output_format, OutputFormat::Table);
+
+        let c = Config::new(args(&["--no-config", path]), 1).unwrap();
+        assert!(c.project_globs.is_empty());
+        assert_eq!(c.output_format, OutputFormat::Table);
+        assert!(c.extension_langs.is_empty());
+
+        std::fs::write(dir.path().join(".rlocc.toml"), "exclude = 42\n").unwrap();
+        assert!(matches!(
+            Config::new(args(&[path]), 1),
+            Err(ConfigError::ProjectConfig(..))
+        ));
+    }
+
+    #[test]
+    fn test_usage_features() {
+        for option in &["--lang-file", "--no-config", "table, json"] {
+            assert_eq!(
+                USAGE.contains(option),
+                cfg!(feature = "serde"),
+                "{}",
+                option
+            );
+        }
+        assert!(USAGE.contains("--output-format"));
+        assert!(USAGE.ends_with("Print version information and exit"));
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn test_missing_features() {
+        let missing = |arg: &str| ConfigError::MissingFeature(arg.to_owned(), "serde");
+        assert_eq!(
+            Config::new(args(&["--lang-file", "a.toml"]), 1).unwrap_err(),
+            missing("--lang-file")
+        );
+        assert_eq!(
+            Config::new(args(&["--no-config"]), 1).unwrap_err(),
+            missing("--no-config")
+        );
+        assert_eq!(
+            Config::new(args(&["-o", "JSON"]), 1).unwrap_err(),
+            missing("-o JSON")
+        );
+        assert_eq!(
+            missing("--lang-file").to_string(),
+            "'--lang-file' requires rlocc to be built with the 'serde' feature"
+        );
+    }
+
+    #[test]
+    fn test_flag_errors() {
+        assert_eq!(
+            Config::new(args(&["--help"]), 0).unwrap_err(),
+            ConfigError::Help
+        );
+        assert_eq!(
+            Config::new(args(&["p", "-V"]), 0).unwrap_err(),
+            ConfigError::Version
+        );
+        assert_eq!(
+            Config::new(args(&["--yolo"]), 0).unwrap_err(),
+            ConfigError::UnknownFlag("--yolo".to_owned())
+        );
+        assert_eq!(
+            Config::new(args(&["--threads"]), 0).unwrap_err(),
+            ConfigError::MissingValue("--threads".to_owned())
+        );
+        assert_eq!(
+            Config::new(args(&["--threads=0"]), 0).unwrap_err(),
+            ConfigError::InvalidValue("--threads".to_owned(), "0".to_owned())
+        );
+        assert_eq!(
+            Config::new(args(&["--queue-capacity", "0"]), 0).unwrap_err(),
+            ConfigError::InvalidValue("--queue-capacity".to_owned(), "0".to_owned())
+        );
+        assert_eq!(
+            Config::new(args(&["-s", "size"]), 0).unwrap_err(),
+            ConfigError::InvalidValue("-s".to_owned(), "size".to_owned())
+        );
+        assert_eq!(
+            Config::new(args(&["--include", "a{b"]), 0).unwrap_err(),
+            ConfigError::InvalidValue("--include".to_owned(), "a{b".to_owned())
+        );
     }
 }
@@ -15,38 +15,134 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
-use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::iter;
+use std::mem;
 use std::ops;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crossbeam_channel as chan;
 use crossbeam_utils::thread;
+use memmap2::Mmap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
 
+use super::config::{Config, SortBy};
 use super::languages::{self, Language};
+use super::skipped::{SkipReason, SkipReport};
 use super::states::*;
-use super::Config;
+use super::walk::{self, Walker};
 
 /// TODO: Documentation
 const BUF_SIZE: usize = 1 << 16;
 
+/// The size of the buffers of each Worker in low-memory mode; still enough for the first block of
+/// each file to be checked for NUL bytes at once.
+const LOW_MEMORY_BUF_SIZE: usize = 1 << 13;
+
+/// The length of the leading block of each file that is checked for NUL bytes to tell binary
+/// files apart (i.e., the same as git's).
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// The text encodings that files are decoded from, as told by their first block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// UTF-8, possibly with a BOM; invalid sequences are replaced with U+FFFD, so that files in
+    /// other ASCII-compatible encodings (e.g., Latin-1) can still be counted.
+    Utf8 {
+        bom: bool,
+    },
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Tells the encoding of a file from its first block, or returns `None` if it looks like a
+    /// binary file.
+    fn detect(block: &[u8]) -> Option<Self> {
+        match block {
+            [0xEF, 0xBB, 0xBF, ..] => Some(Encoding::Utf8 { bom: true }),
+            [0xFF, 0xFE, ..] => Some(Encoding::Utf16Le),
+            [0xFE, 0xFF, ..] => Some(Encoding::Utf16Be),
+            _ if block.iter().take(BINARY_CHECK_LEN).any(|&b| b == 0) => None,
+            _ => Some(Encoding::Utf8 { bom: false }),
+        }
+    }
+
+    /// Returns the length of the BOM of the encoding, in bytes.
+    #[inline]
+    fn bom_len(self) -> usize {
+        match self {
+            Encoding::Utf8 { bom: true } => 3,
+            Encoding::Utf8 { bom: false } => 0,
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+        }
+    }
+
+    /// Decodes the given UTF-16 bytes (without the BOM), replacing invalid sequences with
+    /// U+FFFD.
+    fn decode_utf16(self, bytes: &[u8]) -> String {
+        let units = bytes.chunks(2).map(|pair| match (self, pair) {
+            (Encoding::Utf16Be, &[hi, lo]) => u16::from_be_bytes([hi, lo]),
+            (_, &[lo, hi]) => u16::from_le_bytes([lo, hi]),
+            _ => 0xFFFD, // odd trailing byte
+        });
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
 /// TODO: Documentation
-#[derive(Debug)]
-pub struct LOCCount<'a>(HashMap<&'a str, (CountResult, usize)>);
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LOCCount<'a> {
+    /// The aggregated result of each language, keyed by its name.
+    #[cfg_attr(feature = "serde", serde(borrow, serialize_with = "serialize_sorted"))]
+    languages: HashMap<&'a str, CountResult>,
+
+    /// The result of each file, only kept in by-file mode.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    files: Vec<FileCount>,
+
+    /// The files that have not been counted.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "SkipReport::is_empty")
+    )]
+    skipped: SkipReport,
+
+    /// The result of each file of unique contents so far, only kept when de-duplicating by
+    /// content until the results of all Workers have been collected. Files of different contents
+    /// may still share the same `ContentId`, if their hashes collide.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unique: HashMap<ContentId, Vec<FileCount>>,
+}
 
 impl<'a> ops::AddAssign<CountResult> for LOCCount<'a> {
     /// Add-assign a `self::CountResult` to the `self::LOCCount`.
     #[inline]
     fn add_assign(&mut self, rhs: CountResult) {
-        self.0
+        self.languages
             .entry(rhs.lang)
-            .and_modify(|(cnt_res, num_files)| {
-                *cnt_res += rhs;
-                *num_files += 1;
-            })
-            .or_insert((rhs, 1));
+            .and_modify(|cnt_res| *cnt_res += rhs)
+            .or_insert(rhs);
+    }
+}
+
+impl<'a> ops::AddAssign<FileCount> for LOCCount<'a> {
+    /// Add-assign a `self::FileCount` to the `self::LOCCount`, keeping it around as well.
+    #[inline]
+    fn add_assign(&mut self, rhs: FileCount) {
+        *self += rhs.result;
+        self.files.push(rhs);
     }
 }
 
@@ -54,26 +150,46 @@ impl<'a, 'b: 'a> ops::AddAssign<LOCCount<'b>> for LOCCount<'a> {
     /// Add-assign a `self::LOCCount` to `self::LOCCount`.
     #[inline]
     fn add_assign(&mut self, rhs: LOCCount<'b>) {
-        for (lang, content) in rhs.0.iter() {
-            self.0
+        for (lang, content) in rhs.languages.iter() {
+            self.languages
                 .entry(lang)
-                .and_modify(|(cnt_res, num_files)| {
-                    *cnt_res += content.0;
-                    *num_files += content.1;
-                })
-                .or_insert((content.0, content.1));
+                .and_modify(|cnt_res| *cnt_res += *content)
+                .or_insert(*content);
         }
+        self.files.extend(rhs.files);
+        self.skipped += rhs.skipped;
     }
 }
 
-/// The result of counting a single file.
+/// The result of counting a single file, along with its path.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FileCount {
+    pub path: PathBuf,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub result: CountResult,
+}
+
+/// The result of counting a single file, or the aggregated result of counting multiple files of
+/// the same language.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "CountResultDe")
+)]
 pub struct CountResult {
+    // Deserialized through `CountResultDe`; skipping it here keeps serde from implicitly
+    // borrowing it.
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
     lang: &'static str,
 
+    pub files: usize,
+    #[cfg_attr(feature = "serde", serde(rename = "lines"))]
     pub total: usize,
     pub code: usize,
     pub comments: usize,
+    #[cfg_attr(feature = "serde", serde(rename = "blanks"))]
     pub blank: usize,
 }
 
@@ -96,15 +212,65 @@ pub struct CountResult {
 //    }
 //}
 
+/// Serializes the results of a `self::LOCCount` sorted by language, for the sake of reproducible
+/// output.
+#[cfg(feature = "serde")]
+fn serialize_sorted<S>(map: &HashMap<&str, CountResult>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    map.iter()
+        .collect::<std::collections::BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+/// An owned counterpart of `self::CountResult`, only used to deserialize it; deriving
+/// `Deserialize` for the latter directly would only allow borrowing the name of its language from
+/// input that lives for as long as the program does.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct CountResultDe {
+    lang: String,
+    files: usize,
+    lines: usize,
+    code: usize,
+    comments: usize,
+    blanks: usize,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<CountResultDe> for CountResult {
+    type Error = String;
+
+    /// Map the name of the language to the name of the corresponding supported
+    /// `languages::Language`.
+    fn try_from(cr: CountResultDe) -> Result<Self, Self::Error> {
+        let lang = languages::languages()
+            .map(|lang| lang.name)
+            .chain(std::iter::once("Total"))
+            .find(|lang| *lang == cr.lang)
+            .ok_or_else(|| format!("unknown language '{}'", cr.lang))?;
+        Ok(CountResult {
+            lang,
+            files: cr.files,
+            total: cr.lines,
+            code: cr.code,
+            comments: cr.comments,
+            blank: cr.blanks,
+        })
+    }
+}
+
 impl ops::AddAssign for CountResult {
     /// Add-assign a `self::CountResult` to the `self::CountResult`.
     fn add_assign(&mut self, rhs: Self) {
         if cfg!(debug_assertions) {
             // exclude the case of result aggregation
-            if self.lang != "Total" {
+            if !["Total", "Code", "Documentation"].contains(&self.lang) {
                 debug_assert_eq!(self.lang, rhs.lang);
             }
         }
+        self.files += rhs.files;
         self.total += rhs.total;
         self.code += rhs.code;
         self.comments += rhs.comments;
@@ -117,19 +283,26 @@ impl CountResult {
     pub fn new(lang: &'static str) -> Self {
         CountResult {
             lang,
+            files: 0,
             total: 0,
             code: 0,
             comments: 0,
             blank: 0,
         }
     }
+
+    /// Returns the name of the language counted.
+    #[inline]
+    pub fn lang(&self) -> &'static str {
+        self.lang
+    }
 }
 
 /// TODO: Documentation
 #[derive(Debug)]
 struct Coordinator<'coord> {
     config: &'coord Config,
-    tx: chan::Sender<PathBuf>,
+    walker: Walker<'coord>,
     rx: chan::Receiver<LOCCount<'coord>>,
 }
 
@@ -137,86 +310,96 @@ impl<'coord> Coordinator<'coord> {
     /// Entry point for the Coordinator thread.
     #[inline]
     fn run(self) -> io::Result<LOCCount<'coord>> {
-        self.walk_paths()?;
-        self.aggregate_results()
+        let mut skipped = SkipReport::default();
+        self.walker.walk_paths(&mut skipped);
+        let mut ret = self.aggregate_results()?;
+        ret.skipped += skipped;
+        ret.skipped.files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(ret)
     }
 
-    /// Drop the sending end of the path channel and loop through workers threads' results,
-    /// aggregating them in a `rlocc::LOCCount`.
+    /// Drop the Coordinator's handle to the work queue and loop through workers threads'
+    /// results, aggregating them in a `rlocc::LOCCount`.
     #[inline]
     fn aggregate_results(self) -> io::Result<LOCCount<'coord>> {
-        // Drop the sending-end of the channel to signal workers that
-        // they will not be receiving any more paths to process.
-        drop(self.tx);
+        drop(self.walker);
 
         // Now loop over the receiving-end of the results channel, aggregating all of them into the
         // final LOCCount object that is going to be returned.
-        let mut ret: LOCCount<'coord> = LOCCount(HashMap::new());
+        let mut ret: LOCCount<'coord> = LOCCount::default();
         rlocc_dbg_log!("[Coordinator][aggregate_results] Blocking on res_rx...");
-        while let Ok(res) = self.rx.recv() {
+        while let Ok(mut res) = self.rx.recv() {
             rlocc_dbg_log!(
                 "[Coordinator][aggregate_results] Received '{:?}'. Blocking on res_rx again...",
                 res
             );
+            for (id, files) in mem::take(&mut res.unique) {
+                for file in files {
+                    ret.add_unique(id, file, self.config.keeps_skipped_paths());
+                }
+            }
             ret += res;
         }
         rlocc_dbg_log!("[Coordinator][aggregate_results] res_rs looks disconnected and empty!");
+        // Only the first path of each of the unique contents is counted, regardless of which
+        // Worker happened to count which of their copies.
+        for file in mem::take(&mut ret.unique).into_values().flatten() {
+            if self.config.by_file {
+                ret += file;
+            } else {
+                ret += file.result;
+            }
+        }
+        ret.files.sort_by(|a, b| a.path.cmp(&b.path));
         Ok(ret)
     }
+}
+
+/// Identifies the contents of a file counted in some language, when de-duplicating by content:
+/// the name of the language, the length of the contents and their hash.
+type ContentId = (&'static str, usize, u64);
+
+/// The 64-bit FNV-1a hash of the contents of a file. Unlike `DefaultHasher`, its output is fixed,
+/// so that files are told apart the same way across runs and builds.
+#[derive(Debug, Clone, Copy)]
+struct ContentHasher(u64);
+
+impl ContentHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
 
-    /// Walk the filesystem paths given and feed the worker threads with all related subdirectories
-    /// and files.
     #[inline]
-    fn walk_paths(&self) -> io::Result<()> {
-        for path in self.config.paths.iter() {
-            if path.is_file() {
-                rlocc_dbg_log!("[Coordinator][walk_paths] Sending {:?}...", path);
-                self.tx.send(path.to_owned()).unwrap(); // FIXME error handling?
-            } else if path.is_dir() && !languages::is_vcs(&path) {
-                rlocc_dbg_log!("[Coordinator][walk_paths] Diving into {:?}...", path);
-                self.__walk(path)?;
-            } else {
-                rlocc_dbg_log!(
-                    "[Coordinator][walk_paths] Skipping non-regular file {:?}.",
-                    path
-                );
-            }
-        }
-        Ok(())
+    fn new() -> Self {
+        ContentHasher(Self::OFFSET_BASIS)
     }
 
-    /// Auxiliary method used by `self::Coordinator::walk_paths()` to implement recursive
-    /// filesystem walk.
-    fn __walk(&self, path: &PathBuf) -> io::Result<()> {
-        for direntry in fs::read_dir(path)? {
-            let direntry = direntry?.path();
-            if direntry.is_file() {
-                rlocc_dbg_log!("[Coordinator][__walk] Sending {:?}...", direntry);
-                self.tx.send(direntry).unwrap();
-            } else if direntry.is_dir() && !languages::is_vcs(&direntry) {
-                rlocc_dbg_log!("[Coordinator][__walk] Diving into {:?}...", direntry);
-                self.__walk(&direntry)?;
-            } else {
-                rlocc_dbg_log!(
-                    "[Coordinator][__walk] Skipping non-regular file {:?}.",
-                    direntry
-                );
-            }
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ u64::from(b)).wrapping_mul(Self::PRIME);
         }
-        Ok(())
+    }
+
+    #[inline]
+    fn finish(self) -> u64 {
+        self.0
     }
 }
 
-/// TODO: Documentation
+/// The state of a single line as it is being processed by a `LOCStateMachine`.
 #[derive(Debug)]
 pub struct ParsingState<'line> {
+    /// The remainder of the line that has yet to be processed, with any leading whitespace
+    /// trimmed; must be set before each call to `LOCStateMachine::process`.
     pub curr_line: Option<&'line str>,
+    /// Whether the line has already been counted as code or comment.
     pub curr_line_counted: bool,
+    /// The language of the file that the line belongs to.
     pub curr_lang: &'line Language,
 }
 
 impl<'line> ParsingState<'line> {
-    /// TODO: Documentation
+    /// Creates the state of a line of the given language, which has yet to be set.
     #[inline]
     pub fn new(lang: &'line Language) -> Self {
         ParsingState {
@@ -231,53 +414,28 @@ impl<'line> ParsingState<'line> {
 #[derive(Debug)]
 struct Worker<'w> {
     id: usize,
+    config: &'w Config,
     tx: chan::Sender<LOCCount<'w>>,
-    rx: chan::Receiver<PathBuf>,
+    walker: Walker<'w>,
+    buf_size: usize,
     sm: LOCStateMachine,
     buffer: String,
+    bytes: Vec<u8>,
+    /// The identity of the contents of the file counted last, only kept when de-duplicating by
+    /// content.
+    content: Option<ContentId>,
 }
 
-impl<'w, 'line, 'worker: 'line> Worker<'w> {
+impl<'w> Worker<'w> {
     /// Entry point for each Worker thread.
     fn run(mut self) -> io::Result<()> {
-        rlocc_dbg_log!("[Worker-{}][run] Blocking on paths_rx...", self.id);
-        let mut ret = LOCCount(HashMap::new());
-        while let Ok(path) = self.rx.recv() {
-            rlocc_dbg_log!(
-                "[Worker-{}][run] Received {:?} from paths_rx!",
-                self.id,
-                path
-            );
-
-            match self.process_file(&path) {
-                Ok(res) => {
-                    rlocc_dbg_log!(
-                        "[Worker-{}][run] Calculation for file {:?} has been completed!",
-                        self.id,
-                        path
-                    );
-                    ret += res;
-                    rlocc_dbg_log!(
-                        "[Worker-{}][run] Sent! Now blocking on paths_rx again...",
-                        self.id
-                    );
-                }
-                Err(_err) => {
-                    rlocc_dbg_log!(
-                        "[Worker-{}][run] Error while processing file {:?}: {:#?}",
-                        self.id,
-                        path,
-                        _err
-                    );
-                }
-            };
-        }
-        rlocc_dbg_log!(
-            "[Worker-{}][run] paths_rx looks disconnected and empty!",
-            self.id
-        );
-        // At this point, the paths' channel must have been disconnected by the Coordinator and
-        // emptied by the Worker(s).
+        rlocc_dbg_log!("[Worker-{}][run] Blocking on the work queue...", self.id);
+        let mut ret = LOCCount::default();
+        let mut skipped = SkipReport::default();
+        let walker = self.walker.clone();
+        walker.run(&mut skipped, &mut |path| self.count_file(path, &mut ret));
+        ret.skipped += skipped;
+        rlocc_dbg_log!("[Worker-{}][run] No more work to do!", self.id);
 
         rlocc_dbg_log!(
             "[Worker-{}][run] Sending '{:?}' down on res_rx...",
@@ -294,104 +452,349 @@ impl<'w, 'line, 'worker: 'line> Worker<'w> {
         Ok(())
     }
 
-    /// TODO: Documentation
-    fn process_file(&mut self, path: &PathBuf) -> io::Result<CountResult> {
-        let (_, lang) = languages::guess_language(path)?; // FIXME non ext-based guess
+    /// Count the lines of the file at the given path into `ret`, or record why it has been
+    /// skipped.
+    fn count_file(&mut self, path: PathBuf, ret: &mut LOCCount<'w>) {
+        rlocc_dbg_log!("[Worker-{}][count_file] Counting {:?}...", self.id, path);
+        match self.process_file(&path) {
+            Ok(res) => {
+                rlocc_dbg_log!(
+                    "[Worker-{}][count_file] Calculation for file {:?} has been completed!",
+                    self.id,
+                    path
+                );
+                if let Some(id) = self.content.take() {
+                    let file = FileCount { path, result: res };
+                    ret.add_unique(id, file, self.config.keeps_skipped_paths());
+                } else if self.config.by_file {
+                    *ret += FileCount { path, result: res };
+                } else {
+                    *ret += res;
+                }
+            }
+            Err((reason, err)) => {
+                rlocc_dbg_log!(
+                    "[Worker-{}][count_file] Skipping file {:?} ({}): {:#?}",
+                    self.id,
+                    path,
+                    reason,
+                    err
+                );
+                ret.skipped
+                    .add(&path, reason, &err, self.config.keeps_skipped_paths());
+            }
+        }
+    }
+
+    /// Count the lines of the file at the given path. On failure, the reason the file has been
+    /// skipped is returned along with the error.
+    fn process_file(&mut self, path: &PathBuf) -> Result<CountResult, (SkipReason, io::Error)> {
+        let guess = if let Some(name) = self.config.extension_lang(path) {
+            // Extensions explicitly mapped to a language are neither guessed nor disambiguated.
+            let lang = languages::find_language(name).ok_or_else(|| {
+                (
+                    SkipReason::UnsupportedLanguage,
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Unknown language '{}'", name),
+                    ),
+                )
+            })?;
+            Ok(("", lang))
+        } else {
+            match languages::guess_language(path) {
+                Ok((ext, lang)) => Ok((ext, lang)),
+                // Files without an extension may still be scripts, told apart by their shebang.
+                Err(err) if path.extension().is_none() => Err(err),
+                Err(err) => return Err((SkipReason::UnsupportedLanguage, err)),
+            }
+        };
+        match guess {
+            // Files with an extension shared by multiple languages are checked once told apart.
+            Ok((ext, lang)) if !languages::is_ambiguous(ext) => self.check_lang_included(lang)?,
+            _ => {}
+        }
+
+        let mut file = File::open(path).map_err(|err| (SkipReason::Io, err))?;
+        if !self.config.no_mmap {
+            let len = file.metadata().map_err(|err| (SkipReason::Io, err))?.len();
+            if len < self.buf_size as u64 {
+                // Small files are read at once, since mapping them would cost more than copying.
+                self.bytes.clear();
+                file.read_to_end(&mut self.bytes)
+                    .map_err(|err| (SkipReason::Io, err))?;
+                let bytes = mem::take(&mut self.bytes);
+                let ret = self.count_bytes(&bytes, guess);
+                self.bytes = bytes;
+                return ret;
+            }
+            // SAFETY: The file may be modified by another process while mapped, in which case
+            // lines may be miscounted; but the map is only ever read as bytes, so this can cause
+            // no worse than that (or a SIGBUS, if it is truncated).
+            match unsafe { Mmap::map(&file) } {
+                Ok(mmap) => return self.count_bytes(&mmap, guess),
+                Err(_err) => {
+                    rlocc_dbg_log!(
+                        "[worker-{}][process_file] Reading {:?}, as mapping it failed: {}",
+                        self.id,
+                        path,
+                        _err
+                    );
+                }
+            }
+        }
+        self.count_stream(file, guess, path)
+    }
+
+    /// Tells the encoding of a file from its first `block` and its language from its `guess`,
+    /// resolving any ambiguity or shebang through the block. On failure, the reason the file
+    /// has been skipped is returned along with the error.
+    fn resolve_language(
+        &self,
+        guess: io::Result<(&str, &'static Language)>,
+        block: &[u8],
+    ) -> Result<(&'static Language, Encoding), (SkipReason, io::Error)> {
+        let encoding = match Encoding::detect(block) {
+            Some(encoding) => encoding,
+            None => {
+                return Err((
+                    SkipReason::Binary,
+                    io::Error::new(io::ErrorKind::InvalidData, "Binary file"),
+                ))
+            }
+        };
+        let lang = match guess {
+            Ok((ext, lang)) if languages::is_ambiguous(ext) => {
+                let lang = languages::disambiguate_language(ext, &String::from_utf8_lossy(block))
+                    .unwrap_or(lang);
+                self.check_lang_included(lang)?;
+                lang
+            }
+            Ok((_, lang)) => lang,
+            Err(err) => {
+                let first_line = block.split(|&b| b == b'\n').next().unwrap_or_default();
+                let lang = std::str::from_utf8(first_line)
+                    .ok()
+                    .and_then(languages::guess_language_from_shebang)
+                    .ok_or((SkipReason::UnsupportedLanguage, err))?;
+                self.check_lang_included(lang)?;
+                lang
+            }
+        };
+        Ok((lang, encoding))
+    }
+
+    /// Count the lines of a file, given all of its contents (e.g., mapped in memory). Lines are
+    /// split by searching for newlines with `memchr` and are never copied, unless they are not
+    /// valid UTF-8.
+    fn count_bytes(
+        &mut self,
+        data: &[u8],
+        guess: io::Result<(&str, &'static Language)>,
+    ) -> Result<CountResult, (SkipReason, io::Error)> {
+        let (lang, encoding) =
+            self.resolve_language(guess, &data[..data.len().min(self.buf_size)])?;
+        let mut ret = CountResult::new(lang.name);
+        ret.files = 1;
+        self.sm.reset();
+
+        let data = &data[encoding.bom_len().min(data.len())..];
+        let hasher = self.config.dedup_content.then(|| {
+            let mut hasher = ContentHasher::new();
+            hasher.write(data);
+            (hasher, data.len())
+        });
+        if let Encoding::Utf16Le | Encoding::Utf16Be = encoding {
+            let text = encoding.decode_utf16(data);
+            for line in text.split_inclusive('\n') {
+                count_line(&mut self.sm, lang, line, &mut ret);
+            }
+            self.content = content_id(lang, hasher);
+            return Ok(ret);
+        }
+
+        let mut start = 0;
+        let ends = memchr::memchr_iter(b'\n', data).map(|index| index + 1);
+        for end in ends.chain(iter::once(data.len())) {
+            if start == end {
+                break; // the file ends with a newline
+            }
+            let line = &data[start..end];
+            start = end;
+            match std::str::from_utf8(line) {
+                Ok(line) => count_line(&mut self.sm, lang, line, &mut ret),
+                Err(_) => {
+                    self.buffer.clear();
+                    self.buffer.push_str(&String::from_utf8_lossy(line));
+                    count_line(&mut self.sm, lang, &self.buffer, &mut ret);
+                }
+            }
+        }
+
+        self.content = content_id(lang, hasher);
+        Ok(ret)
+    }
+
+    /// Count the lines of the given file, reading it one line at a time.
+    fn count_stream(
+        &mut self,
+        file: File,
+        guess: io::Result<(&str, &'static Language)>,
+        _path: &Path,
+    ) -> Result<CountResult, (SkipReason, io::Error)> {
+        let mut file_rd = BufReader::with_capacity(self.buf_size, file);
+        let block = file_rd.fill_buf().map_err(|err| (SkipReason::Io, err))?;
+        let (lang, encoding) = self.resolve_language(guess, block)?;
         let mut ret = CountResult::new(lang.name);
+        ret.files = 1;
         self.sm.reset();
 
-        let mut file_rd = BufReader::with_capacity(BUF_SIZE, File::open(path)?);
+        file_rd.consume(encoding.bom_len());
+        let mut hasher = self.config.dedup_content.then(|| (ContentHasher::new(), 0));
+        if let Encoding::Utf16Le | Encoding::Utf16Be = encoding {
+            self.bytes.clear();
+            file_rd
+                .read_to_end(&mut self.bytes)
+                .map_err(|err| (SkipReason::Io, err))?;
+            if let Some((hasher, len)) = hasher.as_mut() {
+                hasher.write(&self.bytes);
+                *len += self.bytes.len();
+            }
+            let text = encoding.decode_utf16(&self.bytes);
+            for line in text.split_inclusive('\n') {
+                count_line(&mut self.sm, lang, line, &mut ret);
+            }
+            self.content = content_id(lang, hasher);
+            return Ok(ret);
+        }
+
         loop {
-            let mut ps = ParsingState::new(lang);
-            self.buffer.clear();
-            match file_rd.read_line(&mut self.buffer) {
+            self.bytes.clear();
+            match file_rd.read_until(b'\n', &mut self.bytes) {
                 Ok(0) => {
                     rlocc_dbg_log!(
-                        "[worker-{}][process_file] Reached EOF in file {:?}",
+                        "[worker-{}][count_stream] Reached EOF in file {:?}",
                         self.id,
-                        path
+                        _path
                     );
                     break;
                 }
-                Ok(_) => {
-                    self.process_line(&mut ps, &mut ret)?;
-                    // TODO somehow update self.state ?
-
-                    // FIXME Do I actually need to explicitly drop ps here to keep stack memory
-                    //       from growing crazy until the loop ends? Or does drop only makes sense
-                    //       for memory allocated in the heap? Is the shadowing in the beginning of
-                    //       the loop enough to automatically free the memory for ps in the stack?
-                    //       According to docs.rs, drop() does effectively nothing for types that
-                    //       implement the Copy trait; but might leaving ps non-Copy really be the
-                    //       solution to keep stack allocation low?
-                    // UPDATE According to `std::mem::needs_drop::<ParsingState>()`, dropping ps
-                    //        certainly has no side effect. So, the memory allocated in the stack
-                    //        must probably be redeemed at the end of each loop iteration (probably
-                    //        reused for the new ps in the next iteration). This can't just be an
-                    //        unavoidable memory leak.
-                    // TODO Benchmark memory usage to verify it.
-                    //drop(ps);
+                Ok(n) => {
+                    if let Some((hasher, len)) = hasher.as_mut() {
+                        hasher.write(&self.bytes);
+                        *len += n;
+                    }
+                    self.buffer.clear();
+                    self.buffer.push_str(&String::from_utf8_lossy(&self.bytes));
+                    count_line(&mut self.sm, lang, &self.buffer, &mut ret);
                 }
                 Err(err) => {
                     rlocc_dbg_log!(
-                        "[worker-{}][process_file] Error reading lines in file {:?}: {}",
+                        "[worker-{}][count_stream] Error reading lines in file {:?}: {}",
                         self.id,
-                        path,
+                        _path,
                         err
                     );
-                    return Err(err);
+                    return Err((SkipReason::Io, err));
                 }
             }
         }
 
+        self.content = content_id(lang, hasher);
         Ok(ret)
     }
 
-    /// TODO: Documentation
+    /// Returns an error if files written in the given language should not be counted.
     #[inline]
-    fn process_line(
-        &'worker mut self,
-        ps: &mut ParsingState<'line>,
-        cr: &mut CountResult,
-    ) -> io::Result<()> {
-        ps.curr_line = Some(&self.buffer.trim_start());
-        self.sm.process(ps, cr);
-        cr.total += 1;
-        debug_assert_eq!(cr.total, cr.code + cr.comments + cr.blank);
-        Ok(())
+    fn check_lang_included(&self, lang: &Language) -> Result<(), (SkipReason, io::Error)> {
+        if self.config.is_lang_included(lang.name) {
+            Ok(())
+        } else {
+            Err((SkipReason::ExcludedLanguage, walk::not_included(lang)))
+        }
     }
 }
 
+/// Returns the identity of the contents hashed in the given language, if de-duplicating by
+/// content.
+#[inline]
+fn content_id(lang: &Language, hasher: Option<(ContentHasher, usize)>) -> Option<ContentId> {
+    hasher.map(|(hasher, len)| (lang.name, len, hasher.finish()))
+}
+
+/// Returns whether the files at the given paths have the same contents, as stored. Files that
+/// cannot be read are told apart, so that neither is dropped as a duplicate of the other.
+fn same_contents(a: &Path, b: &Path) -> bool {
+    let open = |path| File::open(path).map(|file| BufReader::with_capacity(BUF_SIZE, file));
+    let (mut a, mut b) = match (open(a), open(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => return false,
+    };
+    loop {
+        let (x, y) = match (a.fill_buf(), b.fill_buf()) {
+            (Ok(x), Ok(y)) => (x, y),
+            _ => return false,
+        };
+        if x.is_empty() || y.is_empty() {
+            return x.is_empty() && y.is_empty();
+        }
+        let n = x.len().min(y.len());
+        if x[..n] != y[..n] {
+            return false;
+        }
+        a.consume(n);
+        b.consume(n);
+    }
+}
+
+/// Count a single line (along with any leading whitespace and trailing newline) in `cr`.
+#[inline]
+fn count_line(sm: &mut LOCStateMachine, lang: &Language, line: &str, cr: &mut CountResult) {
+    let mut ps = ParsingState::new(lang);
+    ps.curr_line = Some(line.trim_start());
+    sm.process(&mut ps, cr);
+    cr.total += 1;
+    debug_assert_eq!(cr.total, cr.code + cr.comments + cr.blank);
+}
+
 /// TODO: Documentation
-pub fn count_all(config: &Config) -> io::Result<LOCCount> {
+pub fn count_all(config: &Config) -> io::Result<LOCCount<'_>> {
     let mut ret: Option<io::Result<LOCCount>> = None;
 
+    let walker = Walker::new(config);
+    let buf_size = if config.low_memory {
+        LOW_MEMORY_BUF_SIZE
+    } else {
+        BUF_SIZE
+    };
     thread::scope(|s| {
-        let (paths_tx, paths_rx) = chan::unbounded();
-        let (res_tx, res_rx) = chan::unbounded();
+        let (res_tx, res_rx) = chan::bounded(config.num_threads);
 
         for id in 0..config.num_threads {
             let tx = res_tx.clone();
-            let rx = paths_rx.clone();
+            let walker = walker.clone();
             s.spawn(move |_| {
                 let worker = Worker {
                     id,
+                    config,
                     tx,
-                    rx,
+                    walker,
+                    buf_size,
                     sm: LOCStateMachine::new(),
-                    buffer: String::with_capacity(BUF_SIZE),
+                    buffer: String::with_capacity(buf_size),
+                    bytes: Vec::with_capacity(buf_size),
+                    content: None,
                 };
 
                 worker.run()
             }); // TODO really ignore thread handles? Find out how does that work
         }
 
-        drop(paths_rx); // Nobody else receives paths; Coordinator is the sender.
         drop(res_tx); // Nobody else sends results; Coordinator is the receiver.
 
         let coord = Coordinator {
             config,
-            tx: paths_tx,
+            walker,
             rx: res_rx,
         };
         ret = Some(coord.run());
@@ -401,7 +804,168 @@ pub fn count_all(config: &Config) -> io::Result<LOCCount> {
     ret.unwrap()
 }
 
+impl<'a> LOCCount<'a> {
+    /// Keeps the result of a file of the given contents, unless a file of a smaller path has the
+    /// same contents, in which case the one of the larger path is recorded as skipped. Since
+    /// hashes may collide, files of the same `ContentId` are compared byte by byte first.
+    fn add_unique(&mut self, id: ContentId, file: FileCount, verbose: bool) {
+        let files = self.unique.entry(id).or_default();
+        let duplicate = match files
+            .iter_mut()
+            .find(|kept| same_contents(&kept.path, &file.path))
+        {
+            None => {
+                files.push(file);
+                return;
+            }
+            Some(kept) if file.path < kept.path => mem::replace(kept, file),
+            Some(_) => file,
+        };
+        let err = io::Error::other("Identical to another file");
+        self.skipped
+            .add(&duplicate.path, SkipReason::Duplicate, &err, verbose);
+    }
+
+    /// Returns the name and the result of each language counted, sorted by the given column.
+    /// Ties are broken alphabetically, by language.
+    pub fn sorted(&self, key: SortBy) -> Vec<(&'a str, &CountResult)> {
+        let column = |cr: &CountResult| match key {
+            SortBy::Language => 0,
+            SortBy::Files => cr.files,
+            SortBy::Lines => cr.total,
+            SortBy::Blanks => cr.blank,
+            SortBy::Comments => cr.comments,
+            SortBy::Code => cr.code,
+        };
+        let mut ret = self
+            .languages
+            .iter()
+            .map(|(lang, cr)| (*lang, cr))
+            .collect::<Vec<_>>();
+        ret.sort_by(|a, b| column(b.1).cmp(&column(a.1)).then_with(|| a.0.cmp(b.0)));
+        ret
+    }
+
+    /// Returns the aggregated result of all languages counted.
+    #[inline]
+    pub fn total(&self) -> CountResult {
+        self.subtotal("Total", |_| true)
+    }
+
+    /// Returns the aggregated result, under the given label, of the languages counted whose names
+    /// satisfy the given predicate.
+    pub fn subtotal<F>(&self, label: &'static str, predicate: F) -> CountResult
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut ret = CountResult::new(label);
+        for (lang, cr) in self.languages.iter() {
+            if predicate(lang) {
+                ret += *cr;
+            }
+        }
+        ret
+    }
+
+    /// Returns the result of each file counted, if counted in by-file mode.
+    #[inline]
+    pub fn files(&self) -> &[FileCount] {
+        &self.files
+    }
+
+    /// Returns the report of the files that have not been counted.
+    #[inline]
+    pub fn skipped(&self) -> &SkipReport {
+        &self.skipped
+    }
+
+    /// Writes the results to the given writer as a JSON object, holding the result of each
+    /// language (keyed by its name), the result of each file (in by-file mode) and their total.
+    /// If any `documentation` languages are given, separate totals for code and documentation are
+    /// included as well.
+    #[cfg(feature = "serde")]
+    pub fn write_json<W: io::Write>(&self, w: W, documentation: &[String]) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Report<'c, 'a> {
+            #[serde(flatten)]
+            loccount: &'c LOCCount<'a>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            code: Option<CountResult>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            documentation: Option<CountResult>,
+            total: CountResult,
+        }
+
+        let (code, documentation) = match self.split_documentation(documentation) {
+            Some((code, documentation)) => (Some(code), Some(documentation)),
+            None => (None, None),
+        };
+        let report = Report {
+            loccount: self,
+            code,
+            documentation,
+            total: self.total(),
+        };
+        serde_json::to_writer_pretty(w, &report).map_err(io::Error::from)
+    }
+
+    /// Returns the separate totals of code and documentation, given the names of the languages
+    /// that count as documentation (case-insensitively), or `None` if none is given.
+    fn split_documentation(&self, documentation: &[String]) -> Option<(CountResult, CountResult)> {
+        if documentation.is_empty() {
+            return None;
+        }
+        let is_documentation = |name: &str| {
+            documentation
+                .iter()
+                .any(|doc| doc.eq_ignore_ascii_case(name))
+        };
+        Some((
+            self.subtotal("Code", |name| !is_documentation(name)),
+            self.subtotal("Documentation", is_documentation),
+        ))
+    }
+
+    /// Returns an object that implements `fmt::Display` to print the results as a table, sorted
+    /// by the given column. In by-file mode, the files of each language are listed under it,
+    /// sorted by their lines of code.
+    #[inline]
+    pub fn table(&self, sort: SortBy) -> LOCCountTable<'_, 'a> {
+        LOCCountTable {
+            loccount: self,
+            sort,
+            documentation: &[],
+        }
+    }
+}
+
+/// Helper struct for printing a `self::LOCCount` as a table, with its rows sorted in some order.
+/// It is created by `self::LOCCount::table()`.
+#[derive(Debug)]
+pub struct LOCCountTable<'c, 'a> {
+    loccount: &'c LOCCount<'a>,
+    sort: SortBy,
+    documentation: &'c [String],
+}
+
+impl<'c> LOCCountTable<'c, '_> {
+    /// Sets the names of the languages that count as documentation, so that separate totals for
+    /// code and documentation are printed before the grand total.
+    #[inline]
+    pub fn documentation(mut self, langs: &'c [String]) -> Self {
+        self.documentation = langs;
+        self
+    }
+}
+
 impl fmt::Display for LOCCount<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.table(SortBy::default()).fmt(f)
+    }
+}
+
+impl fmt::Display for LOCCountTable<'_, '_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         const MAX_OUT_WIDTH: usize = 80;
         const LANG_WIDTH: usize = 25;
@@ -429,16 +993,13 @@ impl fmt::Display for LOCCount<'_> {
             cdw = CODE_WIDTH,
         )?;
         writeln!(f, "{:-^max$}", "", max = MAX_OUT_WIDTH)?;
-        let mut total_cr = CountResult::new("Total");
-        let mut total_files = 0;
-        for (lang_name, (cr, fc)) in &self.0 {
-            total_cr += *cr;
-            total_files += fc;
+        let total_cr = self.loccount.total();
+        let write_row = |f: &mut fmt::Formatter, cr: &CountResult| {
             writeln!(
                 f,
                 "{:<law$}{:>fw$}{:>liw$}{:>bw$}{:>cmw$}{:>cdw$}",
-                lang_name,
-                fc,
+                cr.lang,
+                cr.files,
                 cr.total,
                 cr.blank,
                 cr.comments,
@@ -449,29 +1010,700 @@ impl fmt::Display for LOCCount<'_> {
                 bw = BLANK_WIDTH,
                 cmw = COMM_WIDTH,
                 cdw = CODE_WIDTH,
-            )?;
+            )
+        };
+
+        // In by-file mode, group the files by language, sorted by their lines of code.
+        let mut files_by_lang: HashMap<&str, Vec<&FileCount>> = HashMap::new();
+        for file in &self.loccount.files {
+            files_by_lang
+                .entry(file.result.lang)
+                .or_default()
+                .push(file);
+        }
+        for files in files_by_lang.values_mut() {
+            files.sort_by(|a, b| {
+                b.result
+                    .code
+                    .cmp(&a.result.code)
+                    .then_with(|| a.path.cmp(&b.path))
+            });
+        }
+
+        for (i, (lang_name, cr)) in self.loccount.sorted(self.sort).into_iter().enumerate() {
+            if i > 0 && !files_by_lang.is_empty() {
+                writeln!(f, "{:-^max$}", "", max = MAX_OUT_WIDTH)?;
+            }
+            write_row(f, cr)?;
+            for file in files_by_lang.get(lang_name).into_iter().flatten() {
+                writeln!(
+                    f,
+                    "  {:<pw$}{:>liw$}{:>bw$}{:>cmw$}{:>cdw$}",
+                    fit_path(&file.path, LANG_WIDTH + FILES_WIDTH - 2),
+                    file.result.total,
+                    file.result.blank,
+                    file.result.comments,
+                    file.result.code,
+                    pw = LANG_WIDTH + FILES_WIDTH - 2,
+                    liw = LINES_WIDTH,
+                    bw = BLANK_WIDTH,
+                    cmw = COMM_WIDTH,
+                    cdw = CODE_WIDTH,
+                )?;
+            }
         }
         writeln!(f, "{:-^max$}", "", max = MAX_OUT_WIDTH)?;
-        writeln!(
-            f,
-            "{:<law$}{:>fw$}{:>liw$}{:>bw$}{:>cmw$}{:>cdw$}",
-            total_cr.lang,
-            total_files,
-            total_cr.total,
-            total_cr.blank,
-            total_cr.comments,
-            total_cr.code,
-            law = LANG_WIDTH,
-            fw = FILES_WIDTH,
-            liw = LINES_WIDTH,
-            bw = BLANK_WIDTH,
-            cmw = COMM_WIDTH,
-            cdw = CODE_WIDTH,
-        )?;
+        if let Some((code, documentation)) = self.loccount.split_documentation(self.documentation) {
+            write_row(f, &code)?;
+            write_row(f, &documentation)?;
+            writeln!(f, "{:-^max$}", "", max = MAX_OUT_WIDTH)?;
+        }
+        write_row(f, &total_cr)?;
         write!(f, "{:-^max$}", "", max = MAX_OUT_WIDTH)
     }
 }
 
+/// Returns the given path as a string that fits in `width` characters, by replacing its
+/// beginning with "..." if needed.
+fn fit_path(path: &Path, width: usize) -> Cow<'_, str> {
+    let path = path.to_string_lossy();
+    let len = path.chars().count();
+    if len <= width {
+        path
+    } else {
+        let tail = path.chars().skip(len + 3 - width).collect::<String>();
+        Cow::Owned(format!("...{}", tail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn by_file_table() {
+        let mut loccount = LOCCount::default();
+        for (path, lang, code) in &[
+            ("a.rs", "Rust", 1),
+            ("b.rs", "Rust", 3),
+            ("some/very/long/path/to/a/deeply/nested/file.go", "Go", 2),
+        ] {
+            let mut result = CountResult::new(lang);
+            result.files = 1;
+            result.total = *code;
+            result.code = *code;
+            loccount += FileCount {
+                path: PathBuf::from(path),
+                result,
+            };
+        }
+
+        let table = loccount.table(SortBy::Code).to_string();
+        let rows = table
+            .lines()
+            .filter(|row| !row.starts_with('-'))
+            .map(|row| row.split_whitespace().take(2).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Language", "Files"],
+                vec!["Rust", "2"],
+                vec!["b.rs", "3"],
+                vec!["a.rs", "1"],
+                vec!["Go", "1"],
+                vec!["...path/to/a/deeply/nested/file.go", "2"],
+                vec!["Total", "3"],
+            ]
+        );
+    }
+
+    #[test]
+    fn documentation_subtotals() {
+        let mut loccount = LOCCount::default();
+        for (lang, lines) in &[("Rust", 10), ("Markdown", 4), ("Plain Text", 1)] {
+            let mut result = CountResult::new(lang);
+            result.files = 1;
+            result.total = *lines;
+            result.code = *lines;
+            loccount += result;
+        }
+
+        let documentation = vec!["markdown".to_owned(), "plain text".to_owned()];
+        let table = loccount
+            .table(SortBy::Code)
+            .documentation(&documentation)
+            .to_string();
+        let rows = table
+            .lines()
+            .filter(|row| !row.starts_with('-'))
+            .skip(4)
+            .map(|row| row.split_whitespace().take(2).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Code", "1"],
+                vec!["Documentation", "2"],
+                vec!["Total", "3"]
+            ]
+        );
+        assert!(!loccount.table(SortBy::Code).to_string().contains("Code "));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let mut loccount = LOCCount::default();
+        let mut cr = CountResult::new("Rust");
+        cr.files = 1;
+        cr.total = 6;
+        cr.code = 3;
+        cr.comments = 2;
+        cr.blank = 1;
+        loccount += cr;
+        loccount += cr;
+
+        let json = serde_json::to_string(&loccount).unwrap();
+        assert_eq!(
+            json,
+            r#"{"languages":{"Rust":{"lang":"Rust","files":2,"lines":12,"code":6,"comments":4,"blanks":2}}}"#
+        );
+        let de: LOCCount = serde_json::from_str(&json).unwrap();
+        assert_eq!(de.sorted(SortBy::Code).len(), 1);
+        assert_eq!(de.total().code, 6);
+
+        let mut out = Vec::new();
+        loccount.write_json(&mut out, &[]).unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(report["languages"]["Rust"]["files"], 2);
+        assert_eq!(report["total"]["lang"], "Total");
+        assert_eq!(report["total"]["lines"], 12);
+        assert!(report.get("files").is_none());
+        assert!(report.get("documentation").is_none());
+
+        let mut out = Vec::new();
+        loccount.write_json(&mut out, &["rust".to_owned()]).unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(report["documentation"]["lines"], 12);
+        assert_eq!(report["code"]["lines"], 0);
+
+        loccount += FileCount {
+            path: PathBuf::from("src/main.rs"),
+            result: cr,
+        };
+        let json = serde_json::to_string(&loccount).unwrap();
+        let de: LOCCount = serde_json::from_str(&json).unwrap();
+        assert_eq!(de.files().len(), 1);
+        assert_eq!(de.files()[0].path, PathBuf::from("src/main.rs"));
+        assert_eq!(de.files()[0].result.code, 3);
+        assert_eq!(de.total().files, 3);
+
+        assert!(serde_json::from_str::<CountResult>(
+            r#"{"lang":"Klingon","files":1,"lines":0,"code":0,"comments":0,"blanks":0}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn skipped_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(dir.path().join("a.png"), "").unwrap();
+        fs::write(dir.path().join("b.png"), "").unwrap();
+        fs::write(dir.path().join("blob.rs"), b"\x7fELF\x02\x01\x01\x00").unwrap();
+
+        let config = Config {
+            paths: vec![dir.path().to_owned(), dir.path().join("missing")],
+            num_threads: 2,
+            include_langs: vec!["rust".to_owned()],
+            verbose: true,
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(loccount.total().files, 1);
+
+        let skipped = loccount.skipped();
+        assert_eq!(skipped.total(), 5);
+        assert_eq!(skipped.count(SkipReason::UnsupportedLanguage), 2);
+        assert_eq!(skipped.count(SkipReason::ExcludedLanguage), 1);
+        assert_eq!(skipped.count(SkipReason::Binary), 1);
+        assert_eq!(skipped.count(SkipReason::Io), 1);
+        assert_eq!(skipped.unknown_extensions.get("png"), Some(&2));
+        assert_eq!(skipped.files.len(), 5);
+        assert!(skipped.files.windows(2).all(|w| w[0].path <= w[1].path));
+    }
+
+    #[test]
+    fn shebang_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("run"), "#!/usr/bin/env bash\n\necho hi\n").unwrap();
+        fs::write(dir.path().join("tool"), "#!/usr/bin/python3 -u\nprint()\n").unwrap();
+        fs::write(dir.path().join("notes"), "Some notes.\n").unwrap();
+
+        let mut config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        let sorted = loccount.sorted(SortBy::Language);
+        assert_eq!(
+            sorted.iter().map(|(lang, _)| *lang).collect::<Vec<_>>(),
+            vec!["Python", "Shell"]
+        );
+        assert_eq!((sorted[1].1.code, sorted[1].1.comments), (1, 1));
+        assert_eq!(loccount.skipped().count(SkipReason::UnsupportedLanguage), 1);
+
+        config.include_langs = vec!["shell".to_owned()];
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(loccount.total().files, 1);
+        assert_eq!(loccount.skipped().count(SkipReason::ExcludedLanguage), 1);
+    }
+
+    #[test]
+    fn ambiguous_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.h"), "#ifndef A_H\n#define A_H\n#endif\n").unwrap();
+        fs::write(
+            dir.path().join("b.h"),
+            "#import <Foundation/Foundation.h>\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("b.m"),
+            "#import \"b.h\"\n@implementation B\n@end\n",
+        )
+        .unwrap();
+
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            include_langs: vec!["objective-c".to_owned()],
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        let sorted = loccount.sorted(SortBy::Language);
+        assert_eq!(sorted.len(), 1);
+        assert_eq!((sorted[0].0, sorted[0].1.files), ("Objective-C", 2));
+        assert_eq!(loccount.skipped().count(SkipReason::ExcludedLanguage), 1);
+    }
+
+    #[test]
+    fn extension_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.inc"), "<?php\n// c\n").unwrap();
+        fs::write(
+            dir.path().join("b.h"),
+            "#import <Foundation/Foundation.h>\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("c.x"), "x\n").unwrap();
+
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            extension_langs: [("inc", "php"), ("h", "C"), ("x", "Klingon")]
+                .iter()
+                .map(|(ext, lang)| (ext.to_string(), lang.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        let sorted = loccount.sorted(SortBy::Language);
+        assert_eq!(
+            sorted.iter().map(|(lang, _)| *lang).collect::<Vec<_>>(),
+            vec!["C", "PHP"]
+        );
+        assert_eq!(loccount.skipped().count(SkipReason::UnsupportedLanguage), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/a.rs"), "fn main() {}\n").unwrap();
+        symlink(dir.path(), dir.path().join("src/loop")).unwrap();
+        symlink(dir.path().join("src"), dir.path().join("lib")).unwrap();
+        symlink(dir.path().join("src/a.rs"), dir.path().join("b.rs")).unwrap();
+        symlink(dir.path().join("missing.rs"), dir.path().join("c.rs")).unwrap();
+
+        let mut config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(loccount.total().files, 1);
+        assert!(loccount.skipped().is_empty());
+
+        config.follow_symlinks = true;
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(loccount.total().files, 2);
+        assert_eq!(loccount.skipped().count(SkipReason::Io), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in &["a/x.rs", "b/x.rs", "c/y.rs"] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "// x\nfn main() {}\n").unwrap();
+        }
+        fs::write(dir.path().join("c/z.rs"), "fn z() {}\n").unwrap();
+        fs::hard_link(dir.path().join("c/z.rs"), dir.path().join("z.rs")).unwrap();
+
+        let mut config = Config {
+            paths: vec![dir.path().to_owned(), dir.path().join("c/z.rs")],
+            num_threads: 2,
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(loccount.total().files, 6);
+
+        config.dedup = true;
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(loccount.total().files, 4);
+        assert_eq!(loccount.skipped().count(SkipReason::Duplicate), 2);
+
+        config.dedup_content = true;
+        let loccount = count_all(&config).unwrap();
+        let total = loccount.total();
+        assert_eq!((total.files, total.code, total.comments), (2, 2, 1));
+        assert_eq!(loccount.skipped().count(SkipReason::Duplicate), 4);
+    }
+
+    #[test]
+    fn duplicate_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..32 {
+            let path = dir.path().join(format!("{}/{}/x.rs", i % 4, i));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "// x\nfn main() {}\n").unwrap();
+        }
+
+        // The copy of the smallest path is the one counted, however the copies are shared among
+        // the Workers.
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 4,
+            queue_capacity: Some(2),
+            by_file: true,
+            dedup_content: true,
+            ..Default::default()
+        };
+        for _ in 0..4 {
+            let loccount = count_all(&config).unwrap();
+            let files: Vec<_> = loccount.files().iter().map(|file| &file.path).collect();
+            assert_eq!(files, [&dir.path().join("0/0/x.rs")]);
+            assert_eq!(loccount.total().files, 1);
+            assert_eq!(loccount.skipped().count(SkipReason::Duplicate), 31);
+        }
+    }
+
+    #[test]
+    fn colliding_contents() {
+        // Distinct contents of the same language and length, which collide under FNV-1a.
+        let (a, b) = ("// 76d7165e6fa8e136\n", "// 701980abf150d3ff\n");
+        let hash = |data: &str| {
+            let mut hasher = ContentHasher::new();
+            hasher.write(data.as_bytes());
+            hasher.finish()
+        };
+        assert_eq!((a.len(), hash(a)), (b.len(), hash(b)));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), a).unwrap();
+        fs::write(dir.path().join("b.rs"), b).unwrap();
+        fs::write(dir.path().join("c.rs"), a).unwrap();
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            dedup_content: true,
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        let total = loccount.total();
+        assert_eq!((total.files, total.comments), (2, 2));
+        assert_eq!(loccount.skipped().count(SkipReason::Duplicate), 1);
+    }
+
+    #[test]
+    fn content_hasher() {
+        let mut hasher = ContentHasher::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+
+        // Hashing line by line is the same as hashing the whole contents at once.
+        let mut lines = ContentHasher::new();
+        lines.write(b"fn main() {\n");
+        lines.write(b"}\n");
+        let mut whole = ContentHasher::new();
+        whole.write(b"fn main() {\n}\n");
+        assert_eq!(lines.finish(), whole.finish());
+    }
+
+    #[test]
+    fn parallel_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..64 {
+            let path = dir
+                .path()
+                .join(format!("{}/{}/{}/f{}.rs", i % 2, i % 3, i % 5, i));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "// f\n\nfn f() {}\n".repeat(i + 1)).unwrap();
+        }
+
+        let by_file = |num_threads, queue_capacity| {
+            let config = Config {
+                paths: vec![dir.path().to_owned()],
+                num_threads,
+                queue_capacity,
+                by_file: true,
+                ..Default::default()
+            };
+            let loccount = count_all(&config).unwrap();
+            let files: Vec<_> = loccount
+                .files
+                .iter()
+                .map(|f| (f.path.clone(), f.result.total, f.result.code))
+                .collect();
+            (files, loccount.total().total)
+        };
+        let (files, total) = by_file(1, None);
+        assert_eq!(files.len(), 64);
+        assert_eq!(total, 3 * (1..=64).sum::<usize>());
+        assert_eq!(by_file(8, None), (files.clone(), total));
+        assert_eq!(by_file(1, Some(1)), (files.clone(), total));
+        assert_eq!(by_file(8, Some(2)), (files, total));
+    }
+
+    #[test]
+    fn mmap_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut large = b"\xef\xbb\xbf/* caf\xe9\n * */\n".to_vec();
+        while large.len() <= BUF_SIZE {
+            large.extend_from_slice(b"fn f() {} // f\n\n    let s = \"/*\";\n// c\n");
+        }
+        large.extend_from_slice(b"\r\nx");
+        fs::write(dir.path().join("large.rs"), &large).unwrap();
+        fs::write(dir.path().join("small.rs"), &large[..100]).unwrap();
+
+        let count = |no_mmap| {
+            let config = Config {
+                paths: vec![dir.path().to_owned()],
+                num_threads: 2,
+                by_file: true,
+                no_mmap,
+                ..Default::default()
+            };
+            let loccount = count_all(&config).unwrap();
+            let files: Vec<_> = loccount
+                .files
+                .iter()
+                .map(|f| {
+                    (
+                        f.result.total,
+                        f.result.code,
+                        f.result.comments,
+                        f.result.blank,
+                    )
+                })
+                .collect();
+            files
+        };
+        let files = count(false);
+        assert_eq!(files.len(), 2);
+        let (total, code, comments, blank) = files[0];
+        assert_eq!(total, large.iter().filter(|&&b| b == b'\n').count() + 1);
+        assert_eq!(total, code + comments + blank);
+        assert_eq!(count(true), files);
+    }
+
+    #[test]
+    fn lang_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("b.json"), "{}\n").unwrap();
+        fs::write(dir.path().join("c.md"), "# c\n").unwrap();
+
+        let mut config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            exclude_langs: vec!["json".to_owned(), "md".to_owned()],
+            ..Default::default()
+        };
+        config.resolve_langs().unwrap();
+        let loccount = count_all(&config).unwrap();
+        let sorted = loccount.sorted(SortBy::Language);
+        assert_eq!(
+            sorted.iter().map(|(lang, _)| *lang).collect::<Vec<_>>(),
+            vec!["Rust"]
+        );
+        assert_eq!(loccount.skipped().count(SkipReason::ExcludedLanguage), 2);
+    }
+
+    #[test]
+    fn exclude_and_include_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in &[
+            "main.go",
+            "api.pb.go",
+            "vendor/lib/lib.go",
+            "src/vendor",
+            "src/a_generated.rs",
+            "src/b.rs",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x\n").unwrap();
+        }
+
+        let mut config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            excludes: vec!["vendor/".to_owned(), "*.pb.go".to_owned()],
+            by_file: true,
+            ..Default::default()
+        };
+        let paths = |loccount: &LOCCount| {
+            loccount
+                .files()
+                .iter()
+                .map(|file| file.path.strip_prefix(dir.path()).unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(
+            paths(&loccount),
+            vec![
+                PathBuf::from("main.go"),
+                PathBuf::from("src/a_generated.rs"),
+                PathBuf::from("src/b.rs")
+            ]
+        );
+        // "src/vendor" is a file, unlike the excluded "vendor/", yet in no language.
+        assert_eq!(loccount.skipped().total(), 1);
+
+        config.excludes.push("*_generated.rs".to_owned());
+        config.includes.push("src/".to_owned());
+        let loccount = count_all(&config).unwrap();
+        assert_eq!(paths(&loccount), vec![PathBuf::from("src/b.rs")]);
+        assert_eq!(loccount.skipped().total(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn project_config_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in &[
+            "p/sub/gen/a.rs",
+            "p/sub/b.rs",
+            "p/sub/e.py",
+            "q/r/c.py",
+            "q/r/d.rs",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x\n").unwrap();
+        }
+        fs::write(
+            dir.path().join("p/.rlocc.toml"),
+            "exclude = [\"/sub/gen\"]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("q/.rlocc.toml"), "exclude = [\"*.py\"]\n").unwrap();
+
+        let paths = |paths: &[&str]| {
+            let args = paths
+                .iter()
+                .map(|path| dir.path().join(path).to_str().unwrap().to_owned())
+                .chain(iter::once("--by-file".to_owned()));
+            let config = Config::new(args, 2).unwrap();
+            let loccount = count_all(&config).unwrap();
+            loccount
+                .files()
+                .iter()
+                .map(|file| file.path.strip_prefix(dir.path()).unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        // The patterns of an ancestor's file are anchored to its own directory.
+        assert_eq!(
+            paths(&["p/sub"]),
+            vec![PathBuf::from("p/sub/b.rs"), PathBuf::from("p/sub/e.py")]
+        );
+        // The files of each of the paths apply to it alone.
+        assert_eq!(
+            paths(&["p/sub", "q/r"]),
+            vec![
+                PathBuf::from("p/sub/b.rs"),
+                PathBuf::from("p/sub/e.py"),
+                PathBuf::from("q/r/d.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn encodings() {
+        let source = "// \u{e9}t\u{e9}\nfn main() {}\n\n/* \u{fc}ber */\n";
+        let utf16 = |be: bool| {
+            let mut ret = if be {
+                vec![0xFE, 0xFF]
+            } else {
+                vec![0xFF, 0xFE]
+            };
+            for unit in source.encode_utf16() {
+                ret.extend_from_slice(&if be {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                });
+            }
+            ret
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<(&str, Vec<u8>)> = vec![
+            ("utf8.rs", source.as_bytes().to_vec()),
+            ("bom.rs", [b"\xEF\xBB\xBF", source.as_bytes()].concat()),
+            (
+                "latin1.rs",
+                source.chars().map(|c| c as u8).collect::<Vec<_>>(),
+            ),
+            ("utf16le.rs", utf16(false)),
+            ("utf16be.rs", utf16(true)),
+        ];
+        for (name, contents) in &files {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+
+        let config = Config {
+            paths: vec![dir.path().to_owned()],
+            num_threads: 2,
+            by_file: true,
+            ..Default::default()
+        };
+        let loccount = count_all(&config).unwrap();
+        assert!(loccount.skipped().is_empty());
+        assert_eq!(loccount.files().len(), files.len());
+        for file in loccount.files() {
+            let cr = &file.result;
+            assert_eq!(
+                (cr.total, cr.code, cr.comments, cr.blank),
+                (4, 1, 2, 1),
+                "{:?}",
+                file.path
+            );
+        }
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,376 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+use super::config::ProjectGlobs;
+
+/// The ignore files looked for in each directory walked, in increasing order of precedence.
+static IGNORE_FILES: &[&str] = &[".git/info/exclude", ".gitignore", ".ignore"];
+
+/// A chain of matchers built out of the ignore files found in a directory and in all of its
+/// parents, up to the root of its git repository, plus git's global excludes file at its root.
+#[derive(Debug)]
+pub struct Ignores {
+    parent: Option<Arc<Ignores>>,
+    matcher: Gitignore,
+    /// For the ignore files of the parents of a path given to be walked, which are matched
+    /// against canonical paths: that path as given and its canonical form, so that the paths
+    /// found under it can be translated.
+    base: Option<Arc<(PathBuf, PathBuf)>>,
+}
+
+impl Ignores {
+    /// Returns the root of the chain, made of git's global excludes file, if any.
+    pub fn global() -> Arc<Self> {
+        let (matcher, _err) = Gitignore::global();
+        if let Some(_err) = _err {
+            rlocc_dbg_log!("[Ignores][global] Error in global excludes: {}", _err);
+        }
+        Arc::new(Ignores {
+            parent: None,
+            matcher,
+            base: None,
+        })
+    }
+
+    /// Returns the chain of matchers to be used for the given path to be walked, i.e., the
+    /// current one extended with the ignore files of the parents of `path`, starting from the
+    /// root of the git repository it is in. As with git, the ignore files of its parents are not
+    /// consulted if it is not in a repository (or is the root of one).
+    pub fn add_parents(self: &Arc<Self>, path: &Path) -> Arc<Self> {
+        let canonical = match fs::canonicalize(path) {
+            Ok(canonical) => canonical,
+            Err(_) => return Arc::clone(self),
+        };
+        let repo = match canonical.ancestors().find(|dir| dir.join(".git").exists()) {
+            Some(repo) if repo != canonical => repo.to_owned(),
+            _ => return Arc::clone(self),
+        };
+        let base = Arc::new((path.to_owned(), canonical));
+        let mut parents: Vec<&Path> = base.1.ancestors().skip(1).collect();
+        parents.truncate(parents.iter().position(|dir| *dir == repo).unwrap() + 1);
+
+        let mut ignores = Arc::clone(self);
+        for dir in parents.into_iter().rev() {
+            ignores = ignores.add(dir, Some(&base));
+        }
+        ignores
+    }
+
+    /// Returns the chain of matchers to be used for the entries of the given directory, i.e.,
+    /// the current one extended with the ignore files found in `dir`, if any.
+    pub fn add_dir(self: &Arc<Self>, dir: &Path) -> Arc<Self> {
+        self.add(dir, None)
+    }
+
+    fn add(self: &Arc<Self>, dir: &Path, base: Option<&Arc<(PathBuf, PathBuf)>>) -> Arc<Self> {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in IGNORE_FILES {
+            let path = dir.join(name);
+            if path.is_file() {
+                found = true;
+                if let Some(_err) = builder.add(&path) {
+                    rlocc_dbg_log!("[Ignores][add_dir] Error in {:?}: {}", path, _err);
+                }
+            }
+        }
+        if !found {
+            return Arc::clone(self);
+        }
+
+        match builder.build() {
+            Ok(matcher) => Arc::new(Ignores {
+                parent: Some(Arc::clone(self)),
+                matcher,
+                base: base.cloned(),
+            }),
+            Err(_err) => {
+                rlocc_dbg_log!("[Ignores][add_dir] Error in {:?}: {}", dir, _err);
+                Arc::clone(self)
+            }
+        }
+    }
+
+    /// Returns whether the given path should be ignored. The ignore files of the innermost
+    /// directories take precedence over those of their parents.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignores = Some(self);
+        while let Some(i) = ignores {
+            let canonical = i.base.as_ref().and_then(|base| {
+                let (given, canonical) = &**base;
+                path.strip_prefix(given)
+                    .ok()
+                    .map(|rest| canonical.join(rest))
+            });
+            match i
+                .matcher
+                .matched(canonical.as_deref().unwrap_or(path), is_dir)
+            {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => ignores = i.parent.as_deref(),
+            }
+        }
+        false
+    }
+}
+
+/// The exclude and include glob patterns of a `Config`, compiled for one of the paths to be
+/// walked. The patterns follow the syntax of gitignore files: patterns without a slash match
+/// names at any depth (e.g., `vendor/` or `*.pb.go`), while the rest are anchored to the root,
+/// or to the directory of the project configuration file they are found in.
+#[derive(Debug)]
+pub struct Globs {
+    exclude: Gitignore,
+    include: Gitignore,
+    /// The exclude and include patterns of each project configuration file that applies, which
+    /// are matched against canonical paths.
+    projects: Vec<(Gitignore, Gitignore)>,
+    /// The root as given and its canonical form, so that the paths found under it can be
+    /// translated for the patterns of project configuration files.
+    base: Option<(PathBuf, PathBuf)>,
+}
+
+impl Globs {
+    /// Compiles the given patterns relative to `root`. Any invalid pattern is disregarded.
+    pub fn new(root: &Path, excludes: &[String], includes: &[String]) -> Self {
+        Globs {
+            exclude: Self::build(root, excludes),
+            include: Self::build(root, includes),
+            projects: Vec::new(),
+            base: None,
+        }
+    }
+
+    /// Compiles the patterns of the project configuration files in `root` or its ancestors,
+    /// relative to the directory of each of them; those of any other files are disregarded.
+    pub fn add_projects(&mut self, root: &Path, projects: &[ProjectGlobs]) {
+        if projects.is_empty() {
+            return;
+        }
+        let canonical = match fs::canonicalize(root) {
+            Ok(canonical) => canonical,
+            Err(_) => return,
+        };
+        for project in projects {
+            if canonical.starts_with(&project.dir) {
+                self.projects.push((
+                    Self::build(&project.dir, &project.excludes),
+                    Self::build(&project.dir, &project.includes),
+                ));
+            }
+        }
+        self.base = Some((root.to_owned(), canonical));
+    }
+
+    fn build(root: &Path, patterns: &[String]) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            if let Err(_err) = builder.add_line(None, pattern) {
+                rlocc_dbg_log!("[Globs][build] Invalid pattern {:?}: {}", pattern, _err);
+            }
+        }
+        builder.build().unwrap_or_else(|_err| {
+            rlocc_dbg_log!("[Globs][build] Error in {:?}: {}", patterns, _err);
+            Gitignore::empty()
+        })
+    }
+
+    /// Returns the canonical form of the given path under the root, if there are any patterns
+    /// of project configuration files to match it against.
+    #[inline]
+    fn canonical(&self, path: &Path) -> Option<PathBuf> {
+        let (root, canonical) = self.base.as_ref()?;
+        path.strip_prefix(root)
+            .ok()
+            .map(|rest| canonical.join(rest))
+    }
+
+    /// Returns whether the given file or directory matches any of the exclude patterns, so that
+    /// it is skipped altogether.
+    #[inline]
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.exclude.matched(path, is_dir).is_ignore()
+            || self.canonical(path).is_some_and(|canonical| {
+                self.projects
+                    .iter()
+                    .any(|(exclude, _)| exclude.matched(&canonical, is_dir).is_ignore())
+            })
+    }
+
+    /// Returns whether the given file (under the root) should be counted, i.e., whether there
+    /// are no include patterns or it (or any of its parents) matches any of them.
+    #[inline]
+    pub fn is_included(&self, path: &Path) -> bool {
+        let included = |include: &Gitignore, path: &Path| {
+            include.matched_path_or_any_parents(path, false).is_ignore()
+        };
+        if self.include.is_empty() && self.projects.iter().all(|(_, include)| include.is_empty()) {
+            return true;
+        }
+        included(&self.include, path)
+            || self.canonical(path).is_some_and(|canonical| {
+                self.projects
+                    .iter()
+                    .any(|(_, include)| included(include, &canonical))
+            })
+    }
+}
+
+/// Returns whether the given pattern is a valid gitignore-style glob.
+pub fn is_valid_glob(pattern: &str) -> bool {
+    GitignoreBuilder::new("").add_line(None, pattern).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn hierarchical_ignores() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "*.tmp\n").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n!keep.tmp\n").unwrap();
+        fs::write(root.join("sub/.ignore"), "!*.log\ngen.rs\n").unwrap();
+
+        let ignores = Ignores::global().add_dir(root);
+        assert!(ignores.is_ignored(&root.join("target"), true));
+        assert!(!ignores.is_ignored(&root.join("target"), false));
+        assert!(ignores.is_ignored(&root.join("a.log"), false));
+        assert!(ignores.is_ignored(&root.join("a.tmp"), false));
+        assert!(!ignores.is_ignored(&root.join("keep.tmp"), false));
+        assert!(!ignores.is_ignored(&root.join("gen.rs"), false));
+
+        let sub = root.join("sub");
+        let ignores = ignores.add_dir(&sub);
+        assert!(!ignores.is_ignored(&sub.join("a.log"), false));
+        assert!(ignores.is_ignored(&sub.join("a.tmp"), false));
+        assert!(ignores.is_ignored(&sub.join("gen.rs"), false));
+        assert!(!ignores.is_ignored(&sub.join("main.rs"), false));
+    }
+
+    #[test]
+    fn parent_ignores() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join("repo/.git")).unwrap();
+        fs::create_dir_all(root.join("repo/src/gen")).unwrap();
+        fs::write(root.join(".gitignore"), "*.rs\n").unwrap();
+        fs::write(root.join("repo/.gitignore"), "/src/gen\n*.log\n").unwrap();
+
+        let src = root.join("repo/src");
+        let ignores = Ignores::global().add_parents(&src).add_dir(&src);
+        assert!(ignores.is_ignored(&src.join("gen"), true));
+        assert!(ignores.is_ignored(&src.join("a.log"), false));
+        // The ignore files above the root of the repository are not consulted.
+        assert!(!ignores.is_ignored(&src.join("main.rs"), false));
+
+        // Paths are matched the same, however the walked path is given.
+        let src = root.join("repo/src/../src");
+        let ignores = Ignores::global().add_parents(&src).add_dir(&src);
+        assert!(ignores.is_ignored(&src.join("gen"), true));
+
+        // Outside of a repository, or at its root, the ignore files of parents are not consulted.
+        let ignores = Ignores::global().add_parents(&root.join("repo"));
+        assert!(!ignores.is_ignored(&root.join("repo/a.rs"), false));
+        fs::remove_dir(root.join("repo/.git")).unwrap();
+        let ignores = Ignores::global().add_parents(&src);
+        assert!(!ignores.is_ignored(&src.join("gen"), true));
+    }
+
+    #[test]
+    fn globs() {
+        let root = Path::new("/repo");
+        let globs = Globs::new(
+            root,
+            &[
+                "vendor/".to_owned(),
+                "*_generated.rs".to_owned(),
+                "/docs".to_owned(),
+            ],
+            &["*.rs".to_owned(), "proto/".to_owned()],
+        );
+        assert!(globs.is_excluded(&root.join("vendor"), true));
+        assert!(globs.is_excluded(&root.join("a/vendor"), true));
+        assert!(!globs.is_excluded(&root.join("vendor"), false));
+        assert!(globs.is_excluded(&root.join("src/a_generated.rs"), false));
+        assert!(globs.is_excluded(&root.join("docs"), true));
+        assert!(!globs.is_excluded(&root.join("src/docs"), true));
+
+        assert!(globs.is_included(&root.join("src/main.rs")));
+        assert!(globs.is_included(&root.join("proto/a/b.proto")));
+        assert!(!globs.is_included(&root.join("src/b.proto")));
+        assert!(Globs::new(root, &[], &[]).is_included(&root.join("README.md")));
+
+        assert!(is_valid_glob("**/*.pb.go"));
+    }
+
+    #[test]
+    fn project_globs() {
+        let root = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(root.path()).unwrap();
+        fs::create_dir_all(root.join("p/sub/gen")).unwrap();
+        let projects = [
+            ProjectGlobs {
+                dir: root.join("p"),
+                excludes: vec!["/sub/gen".to_owned()],
+                includes: vec!["*.rs".to_owned()],
+            },
+            ProjectGlobs {
+                dir: root.join("q"),
+                excludes: vec!["*.rs".to_owned()],
+                includes: Vec::new(),
+            },
+        ];
+
+        // The patterns are anchored to the directory of their file, not to the walked path.
+        let sub = root.join("p/sub");
+        let mut globs = Globs::new(&sub, &["/gen".to_owned()], &[]);
+        globs.add_projects(&sub, &projects);
+        assert!(globs.is_excluded(&sub.join("gen"), true));
+        assert!(!globs.is_excluded(&sub.join("a.rs"), false));
+        assert!(!globs.is_excluded(&sub.join("x/sub/gen"), true));
+        assert!(globs.is_included(&sub.join("a.rs")));
+        assert!(!globs.is_included(&sub.join("a.py")));
+
+        // Paths are matched the same, however the walked path is given.
+        let sub = root.join("p/sub/gen/../../sub");
+        let mut globs = Globs::new(&sub, &[], &[]);
+        globs.add_projects(&sub, &projects);
+        assert!(globs.is_excluded(&sub.join("gen"), true));
+        assert!(!globs.is_included(&sub.join("a.py")));
+
+        // The patterns of files in other directories do not apply.
+        let p = root.join("p");
+        let mut globs = Globs::new(&p, &[], &[]);
+        globs.add_projects(&p, &projects[1..]);
+        assert!(!globs.is_excluded(&p.join("a.rs"), false));
+        assert!(globs.is_included(&p.join("a.py")));
+        assert!(!is_valid_glob("a{b"));
+    }
+}
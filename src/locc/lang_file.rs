@@ -0,0 +1,259 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::languages::{self, Language};
+
+/// The names of the language files looked up in rlocc's configuration directory.
+const DEFAULT_LANG_FILES: &[&str] = &["languages.toml", "languages.json"];
+
+/// The contents of a language file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LangFile {
+    #[serde(default, rename = "language")]
+    languages: Vec<LanguageDef>,
+}
+
+/// The definition of a language in a language file; see `languages::Language` for the meaning of
+/// each field. All fields but `name` are optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LanguageDef {
+    name: String,
+    extensions: Vec<String>,
+    file_names: Vec<String>,
+    file_name_patterns: Vec<String>,
+    interpreters: Vec<String>,
+    inline_comment_tokens: Vec<String>,
+    multiline_comment_start_tokens: Vec<String>,
+    multiline_comment_end_tokens: Vec<String>,
+    nested_comment_tokens: Vec<String>,
+    string_tokens: Vec<String>,
+    raw_string_tokens: Vec<String>,
+}
+
+impl LanguageDef {
+    /// Validates the definition and turns it into a `languages::Language`, leaking its contents
+    /// so that they live as long as the built-in languages do.
+    fn into_language(self) -> Result<Language, String> {
+        if self.name.trim().is_empty() {
+            return Err("language without a name".to_owned());
+        }
+        let tokens = [
+            &self.inline_comment_tokens,
+            &self.multiline_comment_start_tokens,
+            &self.multiline_comment_end_tokens,
+            &self.string_tokens,
+            &self.raw_string_tokens,
+        ];
+        if tokens
+            .iter()
+            .any(|tokens| tokens.iter().any(String::is_empty))
+        {
+            return Err(format!("language '{}' has an empty token", self.name));
+        }
+        if self.multiline_comment_start_tokens.len() != self.multiline_comment_end_tokens.len() {
+            return Err(format!(
+                "language '{}' has {} multi-line comment start token(s) but {} end token(s)",
+                self.name,
+                self.multiline_comment_start_tokens.len(),
+                self.multiline_comment_end_tokens.len()
+            ));
+        }
+        if let Some(token) = self
+            .nested_comment_tokens
+            .iter()
+            .find(|token| !self.multiline_comment_start_tokens.contains(token))
+        {
+            return Err(format!(
+                "nested comment token '{}' of language '{}' is not a multi-line comment start token",
+                token, self.name
+            ));
+        }
+
+        Ok(Language {
+            name: leak_str(self.name),
+            extensions: leak_strs(self.extensions),
+            file_names: leak_strs(self.file_names),
+            file_name_patterns: leak_strs(self.file_name_patterns),
+            interpreters: leak_strs(self.interpreters),
+            inline_comment_tokens: leak_strs(self.inline_comment_tokens),
+            multiline_comment_start_tokens: leak_strs(self.multiline_comment_start_tokens),
+            multiline_comment_end_tokens: leak_strs(self.multiline_comment_end_tokens),
+            nested_comment_tokens: leak_strs(self.nested_comment_tokens),
+            string_tokens: leak_strs(self.string_tokens),
+            raw_string_tokens: leak_strs(self.raw_string_tokens),
+        })
+    }
+}
+
+#[inline]
+fn leak_str(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+#[inline]
+fn leak_strs(v: Vec<String>) -> &'static [&'static str] {
+    Box::leak(
+        v.into_iter()
+            .map(leak_str)
+            .collect::<Vec<_>>()
+            .into_boxed_slice(),
+    )
+}
+
+/// Parses the language definitions in the given TOML (or, if its extension is `.json`, JSON)
+/// contents, e.g.:
+///
+/// ```toml
+/// [[language]]
+/// name = "MyDSL"
+/// extensions = ["dsl"]
+/// inline_comment_tokens = ["#"]
+/// string_tokens = ["\""]
+/// ```
+fn parse_lang_file(path: &Path, contents: &str) -> Result<Vec<Language>, String> {
+    let lang_file: LangFile = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(contents).map_err(|err| err.to_string())?
+    } else {
+        toml::from_str(contents).map_err(|err| err.to_string())?
+    };
+    lang_file
+        .languages
+        .into_iter()
+        .map(LanguageDef::into_language)
+        .collect()
+}
+
+/// Loads the language definitions in the TOML or JSON file at the given path.
+pub fn load_lang_file<P>(path: P) -> io::Result<Vec<Language>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    parse_lang_file(path, &contents).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        )
+    })
+}
+
+/// Returns rlocc's configuration directory, i.e., `$XDG_CONFIG_HOME/rlocc` or
+/// `$HOME/.config/rlocc`.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("rlocc"))
+}
+
+/// Loads the language files found in rlocc's configuration directory along with the given ones,
+/// and registers the languages defined in them. A language defined in a later file overrides any
+/// earlier definition with the same name.
+pub fn register_lang_files(paths: &[PathBuf]) -> io::Result<()> {
+    let defaults = config_dir()
+        .into_iter()
+        .flat_map(|dir| DEFAULT_LANG_FILES.iter().map(move |name| dir.join(name)))
+        .filter(|path| path.is_file());
+
+    let mut langs: Vec<Language> = Vec::new();
+    for path in defaults.chain(paths.iter().cloned()) {
+        for lang in load_lang_file(&path)? {
+            langs.retain(|l| !l.name.eq_ignore_ascii_case(lang.name));
+            langs.push(lang);
+        }
+    }
+    if langs.is_empty() {
+        return Ok(());
+    }
+    languages::register_languages(langs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let toml = r##"
+            [[language]]
+            name = "MyDSL"
+            extensions = ["dsl"]
+            file_names = ["Dslfile"]
+            inline_comment_tokens = ["#"]
+            multiline_comment_start_tokens = ["#[", "{-"]
+            multiline_comment_end_tokens = ["]#", "-}"]
+            nested_comment_tokens = ["{-"]
+            string_tokens = ["\""]
+
+            [[language]]
+            name = "Rust"
+            extensions = ["rs"]
+            inline_comment_tokens = ["//"]
+        "##;
+        let langs = parse_lang_file(Path::new("languages.toml"), toml).unwrap();
+        assert_eq!(langs.len(), 2);
+        assert_eq!(langs[0].name, "MyDSL");
+        assert_eq!(langs[0].file_names, &["Dslfile"]);
+        assert_eq!(langs[0].multiline_comment_end_tokens, &["]#", "-}"]);
+        assert_eq!(langs[0].nested_comment_tokens, &["{-"]);
+        assert!(langs[1].multiline_comment_start_tokens.is_empty());
+
+        let json = r#"{"language": [{"name": "MyDSL", "extensions": ["dsl"]}]}"#;
+        let langs = parse_lang_file(Path::new("languages.json"), json).unwrap();
+        assert_eq!(langs[0].extensions, &["dsl"]);
+
+        for invalid in &[
+            "[[language]]\nextensions = [\"dsl\"]",
+            "[[language]]\nname = \"A\"\ncomment_tokens = [\"#\"]",
+            "[[language]]\nname = \"A\"\ninline_comment_tokens = [\"\"]",
+            "[[language]]\nname = \"A\"\nmultiline_comment_start_tokens = [\"/*\"]",
+            "[[language]]\nname = \"A\"\nnested_comment_tokens = [\"/*\"]",
+        ] {
+            assert!(
+                parse_lang_file(Path::new("languages.toml"), invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("langs.toml");
+        fs::write(&path, "[[language]]\nname = \"MyDSL\"\n").unwrap();
+        assert_eq!(load_lang_file(&path).unwrap()[0].name, "MyDSL");
+
+        fs::write(&path, "[[language]]\nname = 42\n").unwrap();
+        let err = load_lang_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with(&path.display().to_string()));
+        assert!(load_lang_file(dir.path().join("missing.toml")).is_err());
+    }
+}
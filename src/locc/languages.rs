@@ -18,6 +18,10 @@
 use std::collections::HashMap;
 use std::io;
 use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+use aho_corasick::{AhoCorasick, MatchKind};
 
 //use lazy_static::lazy_static;
 //
@@ -33,41 +37,328 @@ use std::path::Path;
 //    };
 //}
 
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 
-/// TODO: Documentation
+/// Languages defined by the user, which take precedence over the built-in ones.
+static USER_LANGUAGES: OnceCell<Vec<&'static Language>> = OnceCell::new();
+
+/// Registers the given user-defined languages. Each of them overrides the built-in language with
+/// the same name (case-insensitively), if any, and takes precedence over all built-in languages
+/// for its extensions, file names and interpreters.
+///
+/// This may only be called once, before any language is guessed; otherwise, it fails.
+pub fn register_languages(langs: Vec<Language>) -> io::Result<()> {
+    if USER_LANGUAGES.get().is_some() {
+        return Err(io::Error::other("Languages have already been registered"));
+    }
+    let langs = langs
+        .into_iter()
+        .map(|lang| &*Box::leak(Box::new(lang)))
+        .collect();
+    USER_LANGUAGES
+        .set(langs)
+        .map_err(|_| io::Error::other("Languages have already been registered"))
+}
+
+/// Returns all supported languages, i.e., the user-defined ones (see `register_languages`)
+/// followed by the built-in ones that they do not override.
+pub fn languages() -> impl Iterator<Item = &'static Language> {
+    let user = USER_LANGUAGES.get_or_init(Vec::new);
+    user.iter().copied().chain(
+        LANG_ARRAY
+            .iter()
+            .filter(move |lang| !user.iter().any(|u| u.name.eq_ignore_ascii_case(lang.name))),
+    )
+}
+
+/// Common aliases of languages, besides their names and extensions.
+static LANG_ALIASES: &[(&str, &str)] = &[
+    ("bash", "Shell"),
+    ("csharp", "C#"),
+    ("docker", "Dockerfile"),
+    ("fsharp", "F#"),
+    ("golang", "Go"),
+    ("js", "Javascript"),
+    ("make", "Makefile"),
+    ("objc", "Objective-C"),
+    ("protobuf", "Protocol Buffers"),
+    ("text", "Plain Text"),
+    ("vim", "VimL"),
+    ("wasm", "WebAssembly (text)"),
+];
+
+/// Returns the supported language with the given name, alias (e.g., `cpp`, `js` or `ts`) or
+/// extension, case-insensitively.
+pub fn find_language(name: &str) -> Option<&'static Language> {
+    let name = name.trim();
+    languages()
+        .find(|lang| lang.name.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            let (_, alias) = LANG_ALIASES
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(name))?;
+            languages().find(|lang| lang.name.eq_ignore_ascii_case(alias))
+        })
+        .or_else(|| EXT_TO_LANG.get(name).copied())
+        .or_else(|| EXT_TO_LANG.get(name.to_lowercase().as_str()).copied())
+}
+
+/// Maps extensions to their languages. Extensions shared by multiple languages are mapped to the
+/// first of them in `languages()`, unless told otherwise by `HEURISTICS`.
 pub static EXT_TO_LANG: Lazy<HashMap<&'static str, &'static Language>> = Lazy::new(|| {
     let mut ext2lang = HashMap::new();
-    for lang in LANG_ARRAY.iter() {
+    for lang in languages() {
         for ext in lang.extensions {
-            ext2lang.insert(*ext, lang);
+            ext2lang.entry(*ext).or_insert(lang);
         }
     }
     ext2lang
 });
 
-static GUESSED_FILE_NAMES: &[&str] =
-    &["copying", "dockerfile", ".gitignore", "makefile", "license"];
+/// Maps the names of languages to the matchers of their tokens.
+static TOKEN_MATCHERS: Lazy<HashMap<&'static str, Arc<TokenMatcher>>> = Lazy::new(|| {
+    let mut matchers = HashMap::new();
+    for lang in languages() {
+        matchers
+            .entry(lang.name)
+            .or_insert_with(|| Arc::new(TokenMatcher::new(lang)));
+    }
+    matchers
+});
+
+/// Returns the matcher of the tokens of the given language; it is only built anew for languages
+/// that are not supported (e.g., not registered).
+pub fn token_matcher(lang: &Language) -> Arc<TokenMatcher> {
+    match TOKEN_MATCHERS.get(lang.name) {
+        Some(matcher) if matcher.is_for(lang) => Arc::clone(matcher),
+        _ => Arc::new(TokenMatcher::new(lang)),
+    }
+}
+
+/// The kinds of tokens that may end code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    MultiLineComment,
+    InlineComment,
+    String,
+}
+
+/// The comment and string tokens of a language, compiled into multi-pattern matchers, so that
+/// each line is scanned once no matter how many tokens the language has.
+#[derive(Debug)]
+pub struct TokenMatcher {
+    /// The name of the language, also used to tell whether the matcher is the one for a language.
+    name: &'static str,
+    /// Matches the multi-line comment starting tokens, the inline comment tokens and the string
+    /// delimiters, preferring them in this order when found at the same index.
+    code: AhoCorasick,
+    /// The kind of each pattern of `code`, along with the token itself.
+    code_tokens: Vec<(TokenKind, &'static str)>,
+    /// Matches the multi-line comment ending tokens.
+    multiline_end: AhoCorasick,
+    multiline_end_tokens: &'static [&'static str],
+}
+
+impl TokenMatcher {
+    /// Compiles the tokens of the given language.
+    pub fn new(lang: &Language) -> Self {
+        let code_tokens: Vec<_> = (lang.multiline_comment_start_tokens.iter())
+            .map(|&token| (TokenKind::MultiLineComment, token))
+            .chain(
+                (lang.inline_comment_tokens.iter()).map(|&token| (TokenKind::InlineComment, token)),
+            )
+            .chain(
+                (lang.string_tokens.iter())
+                    .chain(lang.raw_string_tokens)
+                    .map(|&token| (TokenKind::String, token)),
+            )
+            .collect();
+        let build = |tokens: &mut dyn Iterator<Item = &'static str>| {
+            AhoCorasick::builder()
+                .match_kind(MatchKind::LeftmostFirst)
+                .build(tokens)
+                .expect("tokens are too many or too long")
+        };
+        TokenMatcher {
+            name: lang.name,
+            code: build(&mut code_tokens.iter().map(|&(_, token)| token)),
+            code_tokens,
+            multiline_end: build(&mut lang.multiline_comment_end_tokens.iter().copied()),
+            multiline_end_tokens: lang.multiline_comment_end_tokens,
+        }
+    }
+
+    /// Returns whether this is the matcher of the given language.
+    #[inline]
+    pub fn is_for(&self, lang: &Language) -> bool {
+        ptr::eq(self.name, lang.name)
+    }
+
+    /// Searches `line` for the first comment or string token and returns an `std::Option` that
+    /// wraps the index at which it was found, along with its kind and the token itself.
+    #[inline]
+    pub fn find_token(&self, line: &str) -> Option<(usize, TokenKind, &'static str)> {
+        self.code.find(line).map(|m| {
+            let (kind, token) = self.code_tokens[m.pattern().as_usize()];
+            (m.start(), kind, token)
+        })
+    }
+
+    /// Searches `line` for the first multi-line comment ending token and returns an
+    /// `std::Option` that wraps the index at which it was found, along with the token.
+    #[inline]
+    pub fn find_multiline_end(&self, line: &str) -> Option<(usize, &'static str)> {
+        self.multiline_end
+            .find(line)
+            .map(|m| (m.start(), self.multiline_end_tokens[m.pattern().as_usize()]))
+    }
+}
+
+/// The number of leading lines of files inspected by `disambiguate_language`.
+const HEURISTIC_LINES: usize = 200;
+
+/// An (extension, language name, predicate) triple of `HEURISTICS`.
+type Heuristic = (&'static str, &'static str, fn(&str) -> bool);
+
+/// Content heuristics for extensions shared by multiple languages, as `Heuristic` triples. A file
+/// with such an extension is considered to be written in the language of the first triple whose
+/// predicate holds for any of its first lines (with leading whitespace trimmed); if none does, it
+/// falls back to `EXT_TO_LANG`.
+static HEURISTICS: &[Heuristic] = &[
+    ("h", "Objective-C", is_objective_c),
+    ("m", "Objective-C", is_objective_c),
+    ("pl", "Prolog", |line| {
+        line.starts_with(":-") || line.contains(" :- ") || line.ends_with(":-")
+    }),
+    ("v", "Verilog", |line| {
+        [
+            "endmodule",
+            "`timescale",
+            "`include",
+            "`define",
+            "always",
+            "assign ",
+            "wire ",
+            "reg ",
+            "input ",
+            "output ",
+            "inout ",
+        ]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+    }),
+];
+
+/// Objective-C lines that no C or C++ header (nor Matlab file) would contain.
+fn is_objective_c(line: &str) -> bool {
+    [
+        "#import",
+        "@interface",
+        "@implementation",
+        "@protocol",
+        "@property",
+        "@class",
+        "@end",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+}
+
+/// Returns whether the language of files with the given extension has to be told by their
+/// content, through `disambiguate_language`.
+#[inline]
+pub fn is_ambiguous(ext: &str) -> bool {
+    HEURISTICS.iter().any(|(e, _, _)| *e == ext)
+}
+
+/// This function attempts to tell the language of a file with an extension shared by multiple
+/// languages (e.g., `.h`, `.m`, `.pl` or `.v`) from the first lines of its `content`. It returns
+/// `None` if the extension is not ambiguous, or if none of the heuristics matched.
+pub fn disambiguate_language(ext: &str, content: &str) -> Option<&'static Language> {
+    if !is_ambiguous(ext) {
+        return None;
+    }
+    let lines = content
+        .lines()
+        .take(HEURISTIC_LINES)
+        .map(str::trim_start)
+        .collect::<Vec<_>>();
+    HEURISTICS
+        .iter()
+        .filter(|(e, _, _)| *e == ext)
+        .find(|(_, _, predicate)| lines.iter().any(|line| predicate(line)))
+        .and_then(|(_, name, _)| languages().find(|lang| lang.name == *name))
+}
+
+/// Maps the names of interpreters to the languages of the scripts they run.
+pub static INTERPRETER_TO_LANG: Lazy<HashMap<&'static str, &'static Language>> = Lazy::new(|| {
+    let mut interpreter2lang = HashMap::new();
+    for lang in languages() {
+        for interpreter in lang.interpreters {
+            interpreter2lang.entry(*interpreter).or_insert(lang);
+        }
+    }
+    interpreter2lang
+});
+
+/// Maps exact names of files to their languages.
+pub static FILE_NAME_TO_LANG: Lazy<HashMap<&'static str, &'static Language>> = Lazy::new(|| {
+    let mut name2lang = HashMap::new();
+    for lang in languages() {
+        for name in lang.file_names {
+            name2lang.entry(*name).or_insert(lang);
+        }
+    }
+    name2lang
+});
+
+/// Returns whether the given file name matches the given pattern, i.e., a prefix followed by a
+/// `*`, or a `*` followed by a suffix; the `*` has to match at least one character.
+#[inline]
+fn matches_file_name_pattern(pattern: &str, name: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        name.len() > prefix.len() && name.starts_with(prefix)
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        name.len() > suffix.len() && name.ends_with(suffix)
+    } else {
+        name == pattern
+    }
+}
 
 /// This function attempts to figure out whether the given path corresponds to a file whose
 /// contained source code is written in a language supported by rlocc.
 ///
-/// If it is, then the function returns a tuple containing the extension of the file (which was
-/// used to guess the language) and a reference to the associated `rlocc::languages::Language`
-/// struct.
+/// The exact name of the file is looked up first, then the patterns of file names, and finally
+/// its extension. If any of them matches, the function returns a tuple containing the file name
+/// or extension that was used to guess the language and a reference to the associated
+/// `rlocc::languages::Language` struct.
 pub fn guess_language<'a, 'b, P>(path: &'a P) -> io::Result<(&'a str, &'b Language)>
 where
     P: 'a + AsRef<Path>,
 {
     let path = path.as_ref();
+    let basename = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Cannot retrieve file name for {:?}", path),
+        )
+    })?;
+    if let Some(basename) = basename.to_str() {
+        if let Some(lang) = FILE_NAME_TO_LANG.get(basename) {
+            return Ok((basename, *lang));
+        }
+        for lang in languages() {
+            for pattern in lang.file_name_patterns {
+                if matches_file_name_pattern(pattern, basename) {
+                    return Ok((basename, lang));
+                }
+            }
+        }
+    }
+
     if let Some(ext) = path.extension() {
         let ext = match ext.to_str() {
-            None => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Extension contains invalid UTF-8",
-                ))
-            }
+            None => return Err(io::Error::other("Extension contains invalid UTF-8")),
             Some(e) => e,
         };
 
@@ -80,37 +371,44 @@ where
 
         Ok((ext, *lang))
     } else {
-        let mut found: (&str, Option<&&Language>) = ("", None);
-        let basename = path.file_name().ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("Cannot retrieve file name for {:?}", path),
-            )
-        })?;
-        // FIXME A string allocation per each file that is not recognized
-        // will be detrimental to the overall performance
-        // XXX Do we avoid the allocation of a new String using the method
-        // to_string_lossy() and Cow if the basename is already in lowercase?
-        // Anyway, it probably still sucks performance-wise...
-        let basename = basename.to_string_lossy().to_lowercase();
-        //let basename = basename.to_str().unwrap().to_lowercase(); // no lossy
-        for name in GUESSED_FILE_NAMES {
-            if basename.contains(name) {
-                found = (name, EXT_TO_LANG.get(name));
-            }
-        }
-        Ok((
-            found.0,
-            found.1.ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("Unsupported type of file {:?}", path),
-                )
-            })?,
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unsupported type of file {:?}", path),
         ))
     }
 }
 
+/// This function attempts to figure out the language of a script from its first line, if it is a
+/// shebang line naming a known interpreter, either directly (e.g., `#!/bin/bash`) or through
+/// `env` (e.g., `#!/usr/bin/env -S python3 -u`). Any version number trailing the name of the
+/// interpreter (e.g., `python3.8`) is disregarded.
+pub fn guess_language_from_shebang(line: &str) -> Option<&'static Language> {
+    // The flags of env(1) that take a separate argument.
+    const ENV_FLAGS_WITH_ARG: &[&str] = &["-u", "--unset", "-C", "--chdir", "-P"];
+
+    let mut args = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = args.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = loop {
+            let arg = args.next()?;
+            if ENV_FLAGS_WITH_ARG.contains(&arg) {
+                args.next()?;
+            } else if !arg.starts_with('-') && !arg.contains('=') {
+                break arg.rsplit('/').next()?;
+            }
+        };
+    }
+
+    INTERPRETER_TO_LANG
+        .get(interpreter)
+        .or_else(|| {
+            let unversioned =
+                interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+            INTERPRETER_TO_LANG.get(unversioned)
+        })
+        .copied()
+}
+
 /// TODO: Documentation
 pub static VCS_DIRECTORIES: &[&str] = &[
     ".bzr", // bazaar
@@ -141,461 +439,913 @@ where
 pub struct Language {
     pub name: &'static str,
     pub extensions: &'static [&'static str],
+    /// Exact names of files in the language, regardless of their extension (e.g., `Makefile`).
+    pub file_names: &'static [&'static str],
+    /// Patterns of names of files in the language, consisting of a prefix followed by a `*` (e.g.,
+    /// `Dockerfile.*`) or a `*` followed by a suffix. They are tried after `file_names`, but before
+    /// `extensions`.
+    pub file_name_patterns: &'static [&'static str],
+    /// Names of the interpreters that may appear in the shebang line of scripts in the language,
+    /// without any trailing version numbers.
+    pub interpreters: &'static [&'static str],
 
     pub inline_comment_tokens: &'static [&'static str],
     pub multiline_comment_start_tokens: &'static [&'static str],
     pub multiline_comment_end_tokens: &'static [&'static str],
+    /// The subset of `multiline_comment_start_tokens` that start comments which may be nested.
+    pub nested_comment_tokens: &'static [&'static str],
+
+    /// Tokens that delimit string literals within which a backslash escapes the next character.
+    pub string_tokens: &'static [&'static str],
+    /// Tokens that delimit string literals within which no escape sequences are recognized.
+    pub raw_string_tokens: &'static [&'static str],
 }
 
 /// TODO: Documentation
-pub static LANG_ARRAY: [Language; 76] = [
+pub static LANG_ARRAY: [Language; 81] = [
     Language {
         name: "Ada",
         extensions: &["adb", "ads"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["--"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["\""],
     },
     Language {
         name: "Assembly",
         extensions: &["asm", "s", "S"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[";"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Autoconf",
         extensions: &["in"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["dnl", "#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "AWK",
         extensions: &["awk"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["awk", "gawk", "mawk", "nawk"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Batch",
         extensions: &["bat"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["REM", "::"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["\""],
     },
     Language {
         name: "C",
         extensions: &["c"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "C++",
         extensions: &["cc", "C", "cpp", "cxx", "c++"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "C/C++ Header",
         extensions: &["h", "hh", "H", "hpp", "hxx", "h++"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "C#",
         extensions: &["cs"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//", "///"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Clojure",
         extensions: &["clj", "cljs", "cljc", "edn"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["clojure", "bb"],
         inline_comment_tokens: &[";"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "CMake",
-        extensions: &["cmake"], // FIXME CMakeLists.txt sadly goes to Plain Text
+        extensions: &["cmake"],
+        file_names: &["CMakeLists.txt"],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "COBOL",
         extensions: &["cbl", "cob", "cpy", "cobol"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["*>"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["\"", "'"],
     },
     Language {
         name: "Comma-Separated Values",
         extensions: &["csv"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "CSS",
         extensions: &["css"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "D",
         extensions: &["d"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//", "///"],
         multiline_comment_start_tokens: &["/*", "/+"],
         multiline_comment_end_tokens: &["*/", "+/"],
+        nested_comment_tokens: &["/+"],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &["`"],
     },
     Language {
         name: "Dart",
         extensions: &["dart"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["dart"],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &["/*"],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Delphi",
         extensions: &["p", "pp"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["(*", "{"],
         multiline_comment_end_tokens: &["*)", "}"],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["'"],
     },
     Language {
         name: "Dockerfile",
         extensions: &["dockerfile"],
+        file_names: &["Dockerfile", "dockerfile", "Containerfile"],
+        file_name_patterns: &["Dockerfile.*", "*.Dockerfile"],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &["'"],
     },
     Language {
         name: "Eiffel",
         extensions: &["e"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["--"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["\""],
     },
     Language {
         name: "Elm",
         extensions: &["elm"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["--"],
-        multiline_comment_start_tokens: &["{-"], // no nested
-        multiline_comment_end_tokens: &["-}"],   // no nested
+        multiline_comment_start_tokens: &["{-"],
+        multiline_comment_end_tokens: &["-}"],
+        nested_comment_tokens: &["{-"],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Elixir",
         extensions: &["ex", "exs"],
-        inline_comment_tokens: &["%"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["elixir"],
+        inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Erlang",
         extensions: &["erl", "hrl"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["escript"],
         inline_comment_tokens: &["%"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "F#",
         extensions: &["fs", "fsi", "fsx", "fsscript"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["(*"],
         multiline_comment_end_tokens: &["*)"],
+        nested_comment_tokens: &["(*"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: ".gitignore",
-        extensions: &[".gitignore"],
+        extensions: &[],
+        file_names: &[".gitignore"],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Go",
         extensions: &["go"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &["`"],
+    },
+    Language {
+        name: "Groovy",
+        extensions: &["groovy", "gvy", "gradle"],
+        file_names: &["Jenkinsfile"],
+        file_name_patterns: &[],
+        interpreters: &["groovy"],
+        inline_comment_tokens: &["//"],
+        multiline_comment_start_tokens: &["/*"],
+        multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Haskell",
         extensions: &["hs", "lhs"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["runhaskell", "runghc"],
         inline_comment_tokens: &["--"],
-        multiline_comment_start_tokens: &["{-"], // nesting unsupported
-        multiline_comment_end_tokens: &["-}"],   // nesting unsupported
+        multiline_comment_start_tokens: &["{-"],
+        multiline_comment_end_tokens: &["-}"],
+        nested_comment_tokens: &["{-"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "HTML",
         extensions: &["html", "htm"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &["<!--"],
         multiline_comment_end_tokens: &["-->"],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Java",
         extensions: &["java"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Javascript",
         extensions: &["js"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["node", "nodejs"],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'", "`"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "JSON",
         extensions: &["json"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Julia",
         extensions: &["jl"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["julia"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["#="],
         multiline_comment_end_tokens: &["=#"],
+        nested_comment_tokens: &["#="],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Jupyter",
         extensions: &["ipynb", "jpynb"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Kotlin",
         extensions: &["kt", "kts"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &["/*"],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "License",
-        extensions: &["license", "copying"],
+        extensions: &[],
+        file_names: &["LICENSE", "LICENCE", "COPYING", "COPYRIGHT", "UNLICENSE"],
+        file_name_patterns: &["LICENSE-*", "LICENCE-*", "COPYING.*"],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Lisp",
         extensions: &["lisp", "lsp", "fasl"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["sbcl", "clisp", "ecl"],
         inline_comment_tokens: &[";"],
         multiline_comment_start_tokens: &["#|"],
         multiline_comment_end_tokens: &["|#"],
+        nested_comment_tokens: &["#|"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Lua",
         extensions: &["lua"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["lua", "luajit"],
         inline_comment_tokens: &["--"],
         multiline_comment_start_tokens: &["--[["], // NOTE All the funny weird stuff though
         multiline_comment_end_tokens: &["]]"],     // are not supported, including nesting.
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Makefile",
-        extensions: &["makefile", "am"],
+        extensions: &["mk", "mak", "am"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        file_name_patterns: &[],
+        interpreters: &["make", "gmake"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "MAL",
         extensions: &["mal"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Markdown",
         extensions: &["md"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Matlab",
         extensions: &["m"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["%"],
         multiline_comment_start_tokens: &["%{"],
-        multiline_comment_end_tokens: &["}%"],
+        multiline_comment_end_tokens: &["%}"],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["\""],
     },
     Language {
         name: "Nim",
         extensions: &["nim"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["#["],
         multiline_comment_end_tokens: &["]#"],
+        nested_comment_tokens: &["#["],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Nix",
         extensions: &["nix"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "OCaml",
         extensions: &["ml", "mli"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["ocaml"],
         inline_comment_tokens: &[],
-        multiline_comment_start_tokens: &["(*"], // nesting unsupported
-        multiline_comment_end_tokens: &["*)"],   // nesting unsupported
+        multiline_comment_start_tokens: &["(*"],
+        multiline_comment_end_tokens: &["*)"],
+        nested_comment_tokens: &["(*"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
+    },
+    Language {
+        name: "Objective-C",
+        extensions: &["m", "mm", "h"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
+        inline_comment_tokens: &["//"],
+        multiline_comment_start_tokens: &["/*"],
+        multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "OpenCL",
         extensions: &["cl"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Pascal",
         extensions: &["pas"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &["(*", "{"], // (* to } and { to *) are
         multiline_comment_end_tokens: &["*)", "}"],   // valid too, as they should
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["'"],
     },
     Language {
         name: "Perl",
         extensions: &["pl", "pm", "t", "pod"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["perl"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["=begin"], // __END__ unsupport
         multiline_comment_end_tokens: &["=cut"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "PHP",
         extensions: &["php"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["php"],
         inline_comment_tokens: &["#", "//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Plain Text",
         extensions: &["txt", "text"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Pony",
         extensions: &["pony"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "PowerShell",
         extensions: &["ps1"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["pwsh", "powershell"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["<#"],
         multiline_comment_end_tokens: &["#>"],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["\"", "'"],
+    },
+    Language {
+        name: "Prolog",
+        extensions: &["pl", "pro"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
+        inline_comment_tokens: &["%"],
+        multiline_comment_start_tokens: &["/*"],
+        multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Protocol Buffers",
         extensions: &["proto"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Python",
         extensions: &["py"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["python", "pypy"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[r#"""""#, "'''"],
         multiline_comment_end_tokens: &[r#"""""#, "'''"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "R",
         extensions: &["r", "R", "RData", "rds", "rda"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["Rscript"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "ReStructuredText",
         extensions: &["rst"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Ruby",
         extensions: &["rb"],
+        file_names: &["Rakefile", "Gemfile", "Guardfile", "Podfile", "Vagrantfile"],
+        file_name_patterns: &[],
+        interpreters: &["ruby", "jruby"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["=begin"],
         multiline_comment_end_tokens: &["=end"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Rust",
         extensions: &["rs", "rlib"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"], //, "///", "//!"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &["/*"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Scala",
         extensions: &["scala", "sc"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["scala"],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &["/*"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Scheme",
         extensions: &["scm", "ss"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["guile", "racket", "csi", "chibi-scheme"],
         inline_comment_tokens: &[";"],
         multiline_comment_start_tokens: &["#|"],
         multiline_comment_end_tokens: &["|#"],
+        nested_comment_tokens: &["#|"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Sed",
         extensions: &["sed"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["sed"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Shell",
         extensions: &["sh", "bash", "zsh", "fish", "ksh", "csh"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["sh", "bash", "zsh", "ksh", "mksh", "dash", "ash"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &["'"],
     },
     Language {
         name: "SML",
         extensions: &["sml"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[],
         multiline_comment_start_tokens: &["(*"],
         multiline_comment_end_tokens: &["*)"],
+        nested_comment_tokens: &["(*"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Solidity",
         extensions: &["sol"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "SQL",
         extensions: &["sql"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["--"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &["'", "\""],
+    },
+    Language {
+        name: "Starlark",
+        extensions: &["bzl", "star"],
+        file_names: &["BUILD", "BUILD.bazel", "WORKSPACE", "WORKSPACE.bazel"],
+        file_name_patterns: &[],
+        interpreters: &[],
+        inline_comment_tokens: &["#"],
+        multiline_comment_start_tokens: &[r#"""""#, "'''"],
+        multiline_comment_end_tokens: &[r#"""""#, "'''"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Swift",
         extensions: &["swift"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["swift"],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &["/*"],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Systemd",
@@ -613,86 +1363,171 @@ pub static LANG_ARRAY: [Language; 76] = [
             "target",
             "timer",
         ],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "TeX",
         extensions: &["tex"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["%"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Tcl",
         extensions: &["tcl", "tbc"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["tclsh", "wish"],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "TOML",
         extensions: &["toml"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &["'"],
     },
     Language {
         name: "TypeScript",
         extensions: &["ts"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &["ts-node", "deno"],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'", "`"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "V",
         extensions: &["v"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'", "`"],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Vala",
         extensions: &["vala"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &["/*"],
         multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
+    },
+    Language {
+        name: "Verilog",
+        extensions: &["v", "vh", "sv", "svh"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
+        inline_comment_tokens: &["//"],
+        multiline_comment_start_tokens: &["/*"],
+        multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "VimL",
         extensions: &["vim"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["\""],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "WebAssembly (text)",
         extensions: &["wat"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &[";;"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "YAML",
         extensions: &["yaml", "yml"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\""],
+        raw_string_tokens: &[],
     },
     Language {
         name: "XML",
         extensions: &["xml"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["#"],
         multiline_comment_start_tokens: &["<!--"],
         multiline_comment_end_tokens: &["-->"],
+        nested_comment_tokens: &[],
+        string_tokens: &[],
+        raw_string_tokens: &[],
     },
     Language {
         name: "Zig",
         extensions: &["zig"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
         inline_comment_tokens: &["//"],
         multiline_comment_start_tokens: &[],
         multiline_comment_end_tokens: &[],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
     },
 ];
 
@@ -714,12 +1549,146 @@ mod tests {
     fn dummy_check_hashmap() {
         for lang in LANG_ARRAY.iter() {
             for ext in lang.extensions {
-                assert_eq!(EXT_TO_LANG.get(ext).unwrap().name, lang.name);
+                if EXT_TO_LANG.get(ext).unwrap().name != lang.name {
+                    assert!(HEURISTICS
+                        .iter()
+                        .any(|(e, name, _)| e == ext && *name == lang.name));
+                    continue;
+                }
                 eprintln!("{} --> {}", *ext, lang.name);
             }
         }
     }
 
+    #[test]
+    fn file_names() {
+        for lang in LANG_ARRAY.iter() {
+            for name in lang.file_names {
+                assert_eq!(guess_language(name).unwrap().1.name, lang.name);
+            }
+        }
+        for (path, name) in &[
+            ("a/b/Makefile", Some("Makefile")),
+            ("GNUmakefile", Some("Makefile")),
+            ("CMakeLists.txt", Some("CMake")),
+            ("notes.txt", Some("Plain Text")),
+            ("Dockerfile.dev", Some("Dockerfile")),
+            ("app.Dockerfile", Some("Dockerfile")),
+            ("LICENSE-MIT", Some("License")),
+            ("BUILD.bazel", Some("Starlark")),
+            ("Jenkinsfile", Some("Groovy")),
+            ("Gemfile", Some("Ruby")),
+            ("Makefile.in", Some("Autoconf")),
+            ("my_makefile_notes", None),
+            ("LICENSE-", None),
+            ("Dockerfiles", None),
+        ] {
+            assert_eq!(
+                guess_language(path).ok().map(|(_, lang)| lang.name),
+                *name,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn content_heuristics() {
+        for (ext, content, name) in &[
+            ("h", "#ifndef A_H\n#define A_H\nint a(void);\n#endif\n", None),
+            ("h", "#import <Foundation/Foundation.h>\n@interface A : NSObject\n", Some("Objective-C")),
+            ("m", "function y = f(x)\n  y = x; % identity\nend\n", None),
+            ("m", "#import \"A.h\"\n\n@implementation A\n@end\n", Some("Objective-C")),
+            ("pl", "#!/usr/bin/perl\nuse strict;\nmy $x = 1;\n", None),
+            ("pl", "% facts\nparent(tom, bob).\nancestor(X, Y) :- parent(X, Y).\n", Some("Prolog")),
+            ("v", "module main\n\nfn main() {\n\tprintln('hi')\n}\n", None),
+            ("v", "module counter(input clk, output reg [3:0] q);\n  always @(posedge clk)\n    q <= q + 1;\nendmodule\n", Some("Verilog")),
+            ("rs", "@interface\n", None),
+        ] {
+            assert_eq!(
+                disambiguate_language(ext, content).map(|lang| lang.name),
+                *name,
+                "{}",
+                content
+            );
+        }
+        assert_eq!(EXT_TO_LANG.get("h").unwrap().name, "C/C++ Header");
+        assert_eq!(EXT_TO_LANG.get("m").unwrap().name, "Matlab");
+        assert_eq!(EXT_TO_LANG.get("pl").unwrap().name, "Perl");
+        assert_eq!(EXT_TO_LANG.get("v").unwrap().name, "V");
+    }
+
+    #[test]
+    fn token_matchers() {
+        let lua = EXT_TO_LANG.get("lua").unwrap();
+        let matcher = token_matcher(lua);
+        assert!(Arc::ptr_eq(&matcher, &token_matcher(lua)));
+        assert_eq!(
+            matcher.find_token("x = 1 --[[ a"),
+            Some((6, TokenKind::MultiLineComment, "--[["))
+        );
+        assert_eq!(
+            matcher.find_token("s = '--' -- a"),
+            Some((4, TokenKind::String, "'"))
+        );
+        assert_eq!(matcher.find_token("x = 1"), None);
+        assert_eq!(matcher.find_multiline_end("a ]] b"), Some((2, "]]")));
+
+        let python = EXT_TO_LANG.get("py").unwrap();
+        assert_eq!(
+            token_matcher(python).find_token(r#"""" doc"#),
+            Some((0, TokenKind::MultiLineComment, r#"""""#))
+        );
+        assert!(!matcher.is_for(python));
+    }
+
+    #[test]
+    fn find_languages() {
+        for (name, found) in &[
+            ("rust", Some("Rust")),
+            (" C++ ", Some("C++")),
+            ("cpp", Some("C++")),
+            ("c", Some("C")),
+            ("js", Some("Javascript")),
+            ("TS", Some("TypeScript")),
+            ("golang", Some("Go")),
+            ("plain text", Some("Plain Text")),
+            ("klingon", None),
+        ] {
+            assert_eq!(
+                find_language(name).map(|lang| lang.name),
+                *found,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn shebangs() {
+        for (line, name) in &[
+            ("#!/bin/sh", Some("Shell")),
+            ("#! /bin/bash -e", Some("Shell")),
+            ("#!/usr/bin/perl -w", Some("Perl")),
+            ("#!/usr/bin/env python3", Some("Python")),
+            ("#!/usr/bin/env python3.8\r\n", Some("Python")),
+            ("#!/usr/bin/env -S node --harmony", Some("Javascript")),
+            ("#!/usr/bin/env -i PATH=/bin -u HOME ruby", Some("Ruby")),
+            ("#!/usr/local/bin/lua5.3", Some("Lua")),
+            ("#!/usr/bin/env", None),
+            ("#!/usr/bin/klingon", None),
+            ("# /bin/sh", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                guess_language_from_shebang(line).map(|lang| lang.name),
+                *name,
+                "{}",
+                line
+            );
+        }
+    }
+
     #[test]
     fn print_in_threads() {
         eprintln!("PARENT:\tLANG_ARRAY[13] = {:#?}", LANG_ARRAY[13]);
@@ -15,7 +15,6 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-#[macro_use]
 macro_rules! rlocc_dbg_log {
     ( $s:expr ) => {
         #[cfg(debug_assertions)]
@@ -30,9 +29,24 @@ macro_rules! rlocc_dbg_log {
 
 mod config;
 mod count;
+mod ignores;
+#[cfg(feature = "serde")]
+mod lang_file;
 mod languages;
+#[cfg(feature = "serde")]
+mod project;
+mod skipped;
 mod states;
+mod walk;
 
-pub use self::config::Config;
-pub use self::count::{count_all, LOCCount};
-pub use self::languages::{guess_language, Language};
+pub use self::config::{Config, ConfigError, OutputFormat, ProjectGlobs, SortBy, USAGE};
+pub use self::count::{count_all, CountResult, FileCount, LOCCount, LOCCountTable, ParsingState};
+#[cfg(feature = "serde")]
+pub use self::lang_file::{config_dir, load_lang_file, register_lang_files};
+pub use self::languages::{
+    guess_language, guess_language_from_shebang, languages, register_languages, Language,
+};
+#[cfg(feature = "serde")]
+pub use self::project::{ProjectConfig, PROJECT_CONFIG_FILE};
+pub use self::skipped::{SkipReason, SkipReport, SkippedFile};
+pub use self::states::LOCStateMachine;
@@ -0,0 +1,142 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::config::{ConfigError, OutputFormat};
+use super::ignores;
+
+/// The name of project configuration files.
+pub const PROJECT_CONFIG_FILE: &str = ".rlocc.toml";
+
+/// The settings of a project, as found in its `.rlocc.toml` file(s), e.g.:
+///
+/// ```toml
+/// exclude = ["target/", "vendor/", "*.pb.go"]
+/// include = ["src/"]
+/// documentation = ["Markdown", "Plain Text"]
+/// output_format = "json"
+///
+/// [extensions]
+/// inc = "PHP"
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Glob patterns of files and directories to be skipped.
+    pub exclude: Vec<String>,
+    /// Glob patterns of the only files to be counted.
+    pub include: Vec<String>,
+    /// Extensions (without the leading dot) mapped to the names of the languages of their files,
+    /// overriding any guess.
+    pub extensions: HashMap<String, String>,
+    /// Names of the languages that count as documentation rather than code.
+    pub documentation: Vec<String>,
+    /// The preferred output format.
+    pub output_format: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Parses the project configuration in the file at the given path.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let error = |err: String| ConfigError::ProjectConfig(path.to_owned(), err);
+        let contents = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let ret: Self = toml::from_str(&contents).map_err(|err| error(err.to_string()))?;
+        if let Some(pattern) = ret
+            .exclude
+            .iter()
+            .chain(&ret.include)
+            .find(|pattern| !ignores::is_valid_glob(pattern))
+        {
+            return Err(error(format!("invalid glob pattern '{}'", pattern)));
+        }
+        if let Some(format) = &ret.output_format {
+            format
+                .parse::<OutputFormat>()
+                .map_err(|_| error(format!("invalid output format '{}'", format)))?;
+        }
+        Ok(ret)
+    }
+
+    /// Looks for `.rlocc.toml` files in the given path (or in its directory, if it is a file) and
+    /// all of its ancestors. Returns the settings of each one found along with the (canonical)
+    /// directory it is in, outermost first, so that settings in files closer to the given path can
+    /// take precedence.
+    pub fn discover(path: &Path) -> Result<Vec<(PathBuf, Self)>, ConfigError> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        let dir = if path.is_file() {
+            path.parent().map(Path::to_owned).unwrap_or_default()
+        } else {
+            path
+        };
+
+        let mut ret = Vec::new();
+        for dir in dir.ancestors() {
+            let file = dir.join(PROJECT_CONFIG_FILE);
+            if file.is_file() {
+                ret.push((dir.to_owned(), Self::load(&file)?));
+            }
+        }
+        ret.reverse();
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discover() {
+        let root = tempfile::tempdir().unwrap();
+        let sub = root.path().join("a").join("b");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(sub.join("main.rs"), "fn main() {}\n").unwrap();
+        assert_eq!(ProjectConfig::discover(&sub).unwrap(), vec![]);
+
+        fs::write(
+            root.path().join(PROJECT_CONFIG_FILE),
+            "exclude = [\"target\"]\noutput_format = \"table\"\n\n[extensions]\ninc = \"PHP\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("a").join(PROJECT_CONFIG_FILE),
+            "exclude = [\"vendor\"]\ndocumentation = [\"Markdown\"]\n\n[extensions]\ninc = \"C\"\n",
+        )
+        .unwrap();
+        let projects = ProjectConfig::discover(&sub.join("main.rs")).unwrap();
+        let root = fs::canonicalize(root.path()).unwrap();
+        let dirs: Vec<_> = projects.iter().map(|(dir, _)| dir.clone()).collect();
+        assert_eq!(dirs, vec![root.clone(), root.join("a")]);
+        assert_eq!(projects[0].1.exclude, vec!["target"]);
+        assert_eq!(projects[0].1.output_format.as_deref(), Some("table"));
+        assert_eq!(projects[1].1.exclude, vec!["vendor"]);
+        assert_eq!(projects[1].1.documentation, vec!["Markdown"]);
+        assert_eq!(projects[1].1.extensions.get("inc").unwrap(), "C");
+
+        fs::write(sub.join(PROJECT_CONFIG_FILE), "output_format = \"xml\"\n").unwrap();
+        assert!(ProjectConfig::discover(&sub).is_err());
+        fs::write(sub.join(PROJECT_CONFIG_FILE), "excludes = [\"target\"]\n").unwrap();
+        assert!(ProjectConfig::discover(&sub).is_err());
+        fs::write(sub.join(PROJECT_CONFIG_FILE), "include = [\"a{b\"]\n").unwrap();
+        assert!(ProjectConfig::discover(&sub).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::ops;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The reasons for which a file may not be counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SkipReason {
+    /// The language of the file could not be guessed.
+    UnsupportedLanguage,
+    /// The language of the file has not been included in the count.
+    ExcludedLanguage,
+    /// The file looks like a binary one.
+    Binary,
+    /// The file is the same as, or identical to, another one that has been counted.
+    Duplicate,
+    /// The file (or directory) could not be read.
+    Io,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::UnsupportedLanguage => "unsupported language",
+            SkipReason::ExcludedLanguage => "excluded language",
+            SkipReason::Binary => "binary file",
+            SkipReason::Duplicate => "duplicate file",
+            SkipReason::Io => "I/O error",
+        })
+    }
+}
+
+/// A file (or directory) that has not been counted, along with the reason why.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+    pub error: String,
+}
+
+/// A report of all files (and directories) that have not been counted.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SkipReport {
+    /// The number of files skipped for each reason.
+    pub reasons: BTreeMap<SkipReason, usize>,
+    /// The number of files skipped for each file extension that is not supported.
+    pub unknown_extensions: BTreeMap<String, usize>,
+    /// The files skipped, only kept in verbose mode.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub files: Vec<SkippedFile>,
+}
+
+impl SkipReport {
+    /// Records that the file at `path` was skipped for the given reason, due to the given error.
+    /// The path itself is only kept around if `verbose` is true.
+    pub fn add(&mut self, path: &Path, reason: SkipReason, error: &io::Error, verbose: bool) {
+        *self.reasons.entry(reason).or_insert(0) += 1;
+        if reason == SkipReason::UnsupportedLanguage {
+            if let Some(ext) = path.extension() {
+                *self
+                    .unknown_extensions
+                    .entry(ext.to_string_lossy().into_owned())
+                    .or_insert(0) += 1;
+            }
+        }
+        if verbose {
+            self.files.push(SkippedFile {
+                path: path.to_owned(),
+                reason,
+                error: error.to_string(),
+            });
+        }
+    }
+
+    /// Returns the number of files skipped for the given reason.
+    #[inline]
+    pub fn count(&self, reason: SkipReason) -> usize {
+        self.reasons.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// Returns the total number of files skipped.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.reasons.values().sum()
+    }
+
+    /// Returns true if no files have been skipped.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.reasons.is_empty()
+    }
+}
+
+impl ops::AddAssign for SkipReport {
+    /// Add-assign a `self::SkipReport` to the `self::SkipReport`.
+    fn add_assign(&mut self, rhs: Self) {
+        for (reason, count) in rhs.reasons {
+            *self.reasons.entry(reason).or_insert(0) += count;
+        }
+        for (ext, count) in rhs.unknown_extensions {
+            *self.unknown_extensions.entry(ext).or_insert(0) += count;
+        }
+        self.files.extend(rhs.files);
+    }
+}
+
+impl fmt::Display for SkipReport {
+    /// Writes the number of files skipped for each reason. The alternate form (i.e., `{:#}`)
+    /// also writes the unknown extensions encountered and each file kept, along with its error.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Skipped {} file(s):", self.total())?;
+        for (i, (reason, count)) in self.reasons.iter().enumerate() {
+            write!(f, "{} {} {}", if i > 0 { "," } else { "" }, count, reason)?;
+        }
+        if !f.alternate() {
+            return Ok(());
+        }
+
+        if !self.unknown_extensions.is_empty() {
+            let mut exts = self.unknown_extensions.iter().collect::<Vec<_>>();
+            exts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            write!(f, "\nUnknown extensions:")?;
+            for (i, (ext, count)) in exts.into_iter().enumerate() {
+                write!(f, "{} .{} ({})", if i > 0 { "," } else { "" }, ext, count)?;
+            }
+        }
+
+        for file in &self.files {
+            write!(
+                f,
+                "\n  {}: {}: {}",
+                file.path.display(),
+                file.reason,
+                file.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let err = io::Error::new(io::ErrorKind::NotFound, "Unsupported extension");
+        let mut r1 = SkipReport::default();
+        r1.add(
+            Path::new("a.png"),
+            SkipReason::UnsupportedLanguage,
+            &err,
+            false,
+        );
+        r1.add(
+            Path::new("b.png"),
+            SkipReason::UnsupportedLanguage,
+            &err,
+            false,
+        );
+        r1.add(
+            Path::new("LICENSE-X"),
+            SkipReason::UnsupportedLanguage,
+            &err,
+            false,
+        );
+        let mut r2 = SkipReport::default();
+        r2.add(
+            Path::new("c.lock"),
+            SkipReason::UnsupportedLanguage,
+            &err,
+            true,
+        );
+        let err = io::Error::from(io::ErrorKind::PermissionDenied);
+        r2.add(Path::new("secret.rs"), SkipReason::Io, &err, true);
+        r1 += r2;
+
+        assert_eq!(r1.total(), 5);
+        assert_eq!(r1.count(SkipReason::UnsupportedLanguage), 4);
+        assert_eq!(r1.count(SkipReason::Io), 1);
+        assert_eq!(r1.count(SkipReason::Binary), 0);
+        assert_eq!(r1.files.len(), 2);
+        assert_eq!(
+            r1.to_string(),
+            "Skipped 5 file(s): 4 unsupported language, 1 I/O error"
+        );
+        assert_eq!(
+            format!("{:#}", r1),
+            "Skipped 5 file(s): 4 unsupported language, 1 I/O error\n\
+             Unknown extensions: .png (2), .lock (1)\n  \
+             c.lock: unsupported language: Unsupported extension\n  \
+             secret.rs: I/O error: permission denied"
+        );
+    }
+}
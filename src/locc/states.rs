@@ -15,255 +15,215 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use super::count::{CountResult, ParsingState};
-use super::languages::Language;
+use super::languages::{self, Language, TokenKind, TokenMatcher};
 
-/// TODO Documentation
+/// The state machine that counts each line of a file as code, comment or blank, one (part of a)
+/// line at a time. The state may change multiple times while processing a single line.
 #[derive(Debug)]
 pub struct LOCStateMachine {
-    state: Option<Rc<RefCell<dyn State>>>,
-    states: [Rc<RefCell<dyn State>>; NUM_STATES],
+    state: State,
+    /// The matcher of the tokens of the language of the last line processed.
+    matcher: Option<Arc<TokenMatcher>>,
+}
+
+/// The current state of the LOC counting procedure of a `self::LOCStateMachine`, along with any
+/// data it needs to carry over from one line to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Within code (or at the start of a line), looking for comments and string literals.
+    Code,
+    /// Within a multi-line comment.
+    MultiLineComment {
+        /// The starting token of the current comment, kept around for languages that nest
+        /// comments.
+        start_tkn: &'static str,
+        /// The ending token of the current comment, or `None` if any of the ending tokens of the
+        /// language may end it.
+        end_tkn: Option<&'static str>,
+        /// The nesting depth of the current comment; always 1 for languages that do not nest
+        /// them.
+        depth: usize,
+    },
+    /// Within a string literal, where no comment tokens are recognized.
+    String {
+        /// The delimiter that started the string literal, which is also the one that ends it.
+        token: &'static str,
+    },
+}
+
+impl Default for LOCStateMachine {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LOCStateMachine {
-    /// TODO Documentation
+    /// Returns a new state machine, ready to process the first line of a file.
     #[inline]
     pub fn new() -> Self {
         LOCStateMachine {
-            state: None,
-            states: [
-                Rc::new(RefCell::new(StateMultiLineComment {
-                    tkn_buf: String::with_capacity(8), // > 6 == longest multiline comm token currently
-                    ready: false,
-                })),
-                Rc::new(RefCell::new(StateCode {})),
-            ],
+            state: State::Code,
+            matcher: None,
         }
     }
 
-    /// TODO Documentation
-    #[inline]
-    fn set_state(&mut self, state_no: usize) {
-        rlocc_dbg_log!("[LOCStateMachine][set_state] to {}", state_no);
-        self.state = Some(Rc::clone(&self.states[state_no]));
-    }
-
-    /// TODO Documentation
+    /// Resets the state machine, so that it is ready to process the first line of a file.
     #[inline]
     pub fn reset(&mut self) {
-        self.set_state(STATE_CODE);
+        self.state = State::Code;
     }
 
-    /// TODO Documentation
+    /// Processes the line in the given `ParsingState`, counting it in `res` as code, comment or
+    /// blank (unless it has been already counted) and updating the state for the next line.
     #[inline]
     pub fn process(&mut self, ps: &mut ParsingState, res: &mut CountResult) {
+        let matcher = match self.matcher.take() {
+            Some(matcher) if matcher.is_for(ps.curr_lang) => matcher,
+            _ => languages::token_matcher(ps.curr_lang),
+        };
         loop {
-            rlocc_dbg_log!("[LOCStateMachine][process] re-entering loop...");
-            if let Some(state) = self.state.take() {
-                let mut s = state.borrow_mut();
-                if !s.process(self, ps, res) {
-                    rlocc_dbg_log!(
-                        "[LOCStateMachine][process] state.process loop iteration\tstate = {:?}",
-                        state
-                    );
-                    break;
-                }
+            rlocc_dbg_log!("[LOCStateMachine][process] state = {:?}", self.state);
+            let more = match self.state {
+                State::Code => self.process_code(&matcher, ps, res),
+                State::MultiLineComment {
+                    start_tkn,
+                    end_tkn,
+                    depth,
+                } => self.process_multiline_comment(&matcher, start_tkn, end_tkn, depth, ps, res),
+                State::String { token } => self.process_string(token, ps, res),
+            };
+            if !more {
+                break;
             }
         }
+        self.matcher = Some(matcher);
     }
-}
 
-// FIXME This doesn't look very Rusty...
-const STATE_MULTI_LINE_COMMENT: usize = 0;
-const STATE_CODE: usize = 1;
-const NUM_STATES: usize = 2;
-
-/// TODO Documentation
-fn find_inline(line: &str, lang: &Language) -> Option<usize> {
-    let mut ret: usize = line.len();
-    for &token in lang.inline_comment_tokens {
-        if let Some(index) = line.find(token) {
-            if index < ret {
-                ret = index;
-            }
-        }
-    }
-    if ret != line.len() {
-        Some(ret)
-    } else {
-        None
+    /// Changes to `self::State::MultiLineComment`, for the comment started by the given token.
+    #[inline]
+    fn start_multiline_comment(&mut self, start_tkn: &'static str, lang: &Language) {
+        self.state = State::MultiLineComment {
+            start_tkn,
+            end_tkn: find_multiline_end_token(start_tkn, lang),
+            depth: 1,
+        };
     }
-}
 
-/// TODO More Documentation
-///
-/// Searches `line` for a multi-line comment token (starting or ending, depending on the given
-/// `self::MultiLine` variant) and returns an `std::Option` that wraps the index at which the first
-/// token was found, along with the token.
-fn find_multiline(
-    kind: &mut MultiLine,
-    line: &str,
-    lang: &Language,
-) -> Option<(usize, &'static str)> {
-    let mut ret: (usize, &str) = (line.len(), "");
-
-    let tokens: &[&str] = match kind {
-        MultiLine::Start => &lang.multiline_comment_start_tokens,
-        MultiLine::End((ref mut in_tkn, ref mut ready)) => {
-            if lang.name == "Delphi"
-                || lang.name == "HTML"
-                || lang.name == "Lua"
-                || lang.name == "Pascal"
-                || lang.name == "Perl"
-                || lang.name == "Ruby"
-                || lang.name == "XML"
-            {
-                &lang.multiline_comment_end_tokens
-            } else {
-                if !*ready {
-                    // Construct the multi-line comment end token, without allocating extra memory:
-
-                    // First, take a reference to the given starting token str, the
-                    // value of which should now be the same as the value of in_tkn.
-                    let start_tkn: &str = lang.multiline_comment_start_tokens[lang
-                        .multiline_comment_start_tokens
-                        .iter()
-                        .position(|t| t == in_tkn)
-                        .unwrap()];
-
-                    // Then calculate the corresponding ending token and assign it to in_tkn.
-                    in_tkn.clear();
-                    in_tkn.extend(start_tkn.chars().rev().map(|c| match c {
-                        '(' => ')',
-                        '{' => '}',
-                        '<' => '>',
-                        _ => c,
-                    }));
-
-                    // XXX This was moved in the search() closure:
-                    ////let i = lang
-                    ////    .multiline_comment_end_tokens
-                    ////    .iter()
-                    ////    .position(|&t| t == in_tkn)
-                    ////    .unwrap(); // FIXME
-
-                    ////// ...and subslice that single element.
-                    ////&lang.multiline_comment_end_tokens[i..i + 1]
-
-                    *ready = &true;
-                }
+    /// Processes the line remainder within code. Returns false when done processing the current
+    /// line and ready to move to the next one, or true when there is more processing to be done
+    /// in the same line.
+    fn process_code(
+        &mut self,
+        matcher: &TokenMatcher,
+        ps: &mut ParsingState,
+        cr: &mut CountResult,
+    ) -> bool {
+        rlocc_dbg_log!(
+            "[STATE_CODE][process] line ({}) = {}",
+            cr.total + 1,
+            ps.curr_line.unwrap().trim_end()
+        );
 
-                // Now, find the index of the ending token in the corresponding slice...
-                // FIXME This may not be actually needed, but then cannot return &'static
-                rlocc_dbg_log!(
-                    "[find_multiline] now searching for registered ending token {:?} in {:?}",
-                    in_tkn,
-                    lang
-                );
-                let i = lang
-                    .multiline_comment_end_tokens
-                    .iter()
-                    .position(|t| t == in_tkn);
-                ////    .position(|&t| t == in_tkn)
-                ////    .unwrap(); // FIXME
-
-                ////// ...and subslice that single element.
-                ////&lang.multiline_comment_end_tokens[i..i + 1]
-                //
-                // FIXME This  ^^^  is the correct way to handle this.
-                //       But this  vvv  is the workaround until StateString.
-                //       In other words, the workaround counts wrong if a starting multi-line
-                //       comment token is found within a string in the source file examined.
-                //       This makes it identical to glocc, i.e., wrong in the same way.
-                if let Some(i) = i {
-                    &lang.multiline_comment_end_tokens[i..i + 1]
-                } else {
-                    &lang.multiline_comment_end_tokens
-                }
+        // Whitespace must have already been trimmed in ps.curr_line when populated in
+        // Worker.process_line(), but not in a line remainder handed over by another State.
+        let line_rem = ps.curr_line.unwrap().trim_start();
+        if line_rem.is_empty() {
+            // Count the line as blank (unless it has been already counted, e.g. when it ends
+            // with a string literal) and move on, but remain in State::Code.
+            if !ps.curr_line_counted {
+                rlocc_dbg_log!("[STATE_CODE][process] counting line as blank");
+                cr.blank += 1;
+                ps.curr_line_counted = true;
             }
+            return false; // move on to the next line
         }
-    };
 
-    for &token in tokens {
-        if let Some(index) = line.find(token) {
-            if index < ret.0 {
-                ret = (index, token);
+        // Find the first inline comment token, multiline comment start token or string
+        // delimiter, if any. On a tie, a multiline comment token is preferred over an inline
+        // comment token (e.g., Lua's "--[[" over "--"), and both of them over a string delimiter
+        // (e.g., Python's `"""` over `"`).
+        let first_token = matcher.find_token(line_rem);
+
+        match first_token {
+            Some((0, TokenKind::InlineComment, _)) => {
+                // If the inline comment token is in the beginning of the line, count the
+                // line as a comment, move on to the next line, but remain in State::Code.
+                if !ps.curr_line_counted {
+                    rlocc_dbg_log!("[STATE_CODE][process] counting line as comment");
+                    cr.comments += 1;
+                    ps.curr_line_counted = true;
+                }
+                false // move on to the next line
+            }
+            Some((0, TokenKind::MultiLineComment, token)) => {
+                // If the multiline comment token is in the beginning of the line, don't count
+                // this line yet (since we don't know where the comment ends), but change to
+                // State::MultiLineComment, after updating the line remainder to look past the
+                // found token.
+                ps.curr_line.replace(&line_rem[token.len()..]); // update line remainder
+                self.start_multiline_comment(token, ps.curr_lang);
+                true // keep processing the same line
+            }
+            first_token => {
+                // If the line hasn't been blank and doesn't start with an inline or a multiline
+                // comment, then count it as code (unless it has been already counted), and
+                // figure out the next state.
+                if !ps.curr_line_counted {
+                    rlocc_dbg_log!("[STATE_CODE][process] counting line as code");
+                    cr.code += 1;
+                    ps.curr_line_counted = true;
+                }
+                match first_token {
+                    Some((index, TokenKind::MultiLineComment, token)) => {
+                        // A multiline comment starts in this line (at some point, after the
+                        // code), so update the line remainder, change to
+                        // State::MultiLineComment, and keep processing the same line.
+                        ps.curr_line.replace(&line_rem[index + token.len()..]);
+                        self.start_multiline_comment(token, ps.curr_lang);
+                        true
+                    }
+                    Some((index, TokenKind::String, token))
+                        if is_char_literal(line_rem, index, token) =>
+                    {
+                        // The delimiter is quoted as a character literal (e.g., Rust's '"'),
+                        // so skip past the literal and keep processing the same line.
+                        ps.curr_line.replace(&line_rem[index + token.len() + 1..]);
+                        true
+                    }
+                    Some((index, TokenKind::String, token)) => {
+                        // A string literal starts in this line, so update the line remainder,
+                        // change to State::String, and keep processing the same line.
+                        ps.curr_line.replace(&line_rem[index + token.len()..]);
+                        self.state = State::String { token };
+                        true
+                    }
+                    // The line is either pure code or it starts with code and ends with some
+                    // inline comment, so just move on to the next line.
+                    _ => false,
+                }
             }
         }
     }
 
-    if ret.0 != line.len() {
-        Some(ret)
-    } else {
-        None
-    }
-}
-
-/// The current state of the LOC counting procedure of a `self::LOCStateMachine`.
-/// The state of a Worker may change multiple times while processing a single line.
-trait State: Sync + Send + std::fmt::Debug {
-    /// TODO More Documentation
-    ///
-    /// Returns false when the State is done processing the current line and is ready to move
-    /// to the next one, or true when there is more processing to be done in the same line.
-    fn process(
+    /// Processes the line remainder within a multi-line comment, started by `start_tkn` and
+    /// nested `depth` levels deep. Returns whether there is more processing to be done in the
+    /// same line.
+    fn process_multiline_comment(
         &mut self,
-        sm: &mut LOCStateMachine,
-        ps: &mut ParsingState,
-        cr: &mut CountResult,
-    ) -> bool;
-
-    /// Returns the unique index of this State in every `self::LOCStateMachine`'s `states` array.
-    fn get_state_no(&self) -> usize;
-
-    fn set_token(&mut self, token: &'static str);
-}
-
-/// TODO More Documentation
-#[derive(Debug)]
-struct StateMultiLineComment {
-    /// A state-local buffer to hold either the starting or the ending multi-line comment token
-    /// (found or searched for, respectively).
-    tkn_buf: String,
-
-    /// Indicates whether the persisted `self.tkn_buf` is ready to be searched (i.e., the ending
-    /// token has been already calculated and stored there) or if the calculation is still needed.
-    ready: bool,
-}
-
-/// TODO Documentation
-#[derive(Debug)]
-enum MultiLine<'a> {
-    Start,
-    End((&'a mut String, &'a bool)),
-}
-
-impl State for StateMultiLineComment {
-    #[inline]
-    fn get_state_no(&self) -> usize {
-        STATE_MULTI_LINE_COMMENT
-    }
-
-    #[inline]
-    fn set_token(&mut self, token: &'static str) {
-        self.tkn_buf.truncate(0); // btw, looks like that's what String.clear() boils down too
-        self.tkn_buf.push_str(token);
-        debug_assert_eq!(self.tkn_buf.len(), token.len());
-    }
-
-    /// TODO Documentation
-    fn process(
-        &mut self,
-        sm: &mut LOCStateMachine,
+        matcher: &TokenMatcher,
+        start_tkn: &'static str,
+        end_tkn: Option<&'static str>,
+        depth: usize,
         ps: &mut ParsingState,
         cr: &mut CountResult,
     ) -> bool {
         rlocc_dbg_log!(
             "[STATE_MULTI_LINE_COMMENT][process] state = {:?}\tline ({}) = {}",
-            self,
+            self.state,
             cr.total + 1,
             ps.curr_line.unwrap().trim_end()
         );
@@ -273,182 +233,170 @@ impl State for StateMultiLineComment {
         if line_rem.is_empty() {
             rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT][process] line_rem empty - leaving!");
             // Count the line as (blank|comment) and move on to the next one,
-            // but remain in StateMultiLineComment.
+            // but remain in State::MultiLineComment.
             if !ps.curr_line_counted {
                 rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT] counting line as comment|blank");
                 cr.comments += 1;
                 //cr.blank += 1; // FIXME? comment or blank?
                 ps.curr_line_counted = true;
             }
-            sm.set_state(self.get_state_no()); // FIXME? refactor for StateString or use const?
             return false; // move on to the next line
         }
 
-        rlocc_dbg_log!("starting token = {:?}", self.tkn_buf);
-        let mut end_variant = MultiLine::End((&mut self.tkn_buf, &mut self.ready));
-        let first_multiline_end = find_multiline(&mut end_variant, &line_rem, ps.curr_lang);
-        if let MultiLine::End((_, r)) = end_variant {
-            self.ready = *r;
+        let first_multiline_end = match end_tkn {
+            Some(token) => line_rem.find(token).map(|index| (index, token)),
+            None => matcher.find_multiline_end(line_rem),
+        };
+        rlocc_dbg_log!("first_multiline_end = {:?}", first_multiline_end);
+
+        // If the starting token of the comment may be nested, look for another occurrence of it
+        // before the ending token (if any); if there is one, the comment just got deeper.
+        if ps.curr_lang.nested_comment_tokens.contains(&start_tkn) {
+            if let Some(index) = line_rem.find(start_tkn) {
+                if first_multiline_end.is_none_or(|(end_index, _)| index < end_index) {
+                    rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT] nested comment starts");
+                    self.state = State::MultiLineComment {
+                        start_tkn,
+                        end_tkn,
+                        depth: depth + 1,
+                    };
+                    ps.curr_line.replace(&line_rem[index + start_tkn.len()..]);
+                    return true; // keep processing the same line
+                }
+            }
         }
-        rlocc_dbg_log!(
-            "first_multiline_end = {:?}, self.tkn_buf = {:?}",
-            first_multiline_end,
-            self.tkn_buf
-        );
 
         // Since the line is not blank, if it does not contain the ending token we should
-        // count it as a comment and move on to the next line, remaining in StateMultiLine.
-        if first_multiline_end.is_none() {
-            if !ps.curr_line_counted {
-                rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT] counting multi-line comment line");
-                cr.comments += 1;
-                ps.curr_line_counted = true;
+        // count it as a comment and move on to the next line, remaining in
+        // State::MultiLineComment.
+        let (index, token) = match first_multiline_end {
+            Some(found) => found,
+            None => {
+                if !ps.curr_line_counted {
+                    rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT] counting multi-line comment line");
+                    cr.comments += 1;
+                    ps.curr_line_counted = true;
+                }
+                return false;
             }
-            sm.set_state(self.get_state_no()); // FIXME? refactor for StateString or use const?
-            return false;
-        }
+        };
 
         // The ending token has been found within this line, but we need to make sure that
         // there is no code in the same line before actualling counting it as a comment.
-        let (index, token) = first_multiline_end.unwrap();
+        // If it is a nested comment that ends, then the enclosing one goes on, so keep
+        // processing the line remainder in State::MultiLineComment.
+        if depth > 1 {
+            rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT] nested comment ends");
+            self.state = State::MultiLineComment {
+                start_tkn,
+                end_tkn,
+                depth: depth - 1,
+            };
+            ps.curr_line.replace(&line_rem[index + token.len()..]);
+            return true; // keep processing the same line
+        }
+        self.state = State::Code;
         // If the ending token is at the end of the line remainder, then we are good
-        // to count the line as a comment and move on to the next line, in StateCode.
+        // to count the line as a comment and move on to the next line, in State::Code.
         if index + token.len() == line_rem.len() {
             if !ps.curr_line_counted {
                 rlocc_dbg_log!("[STATE_MULTI_LINE_COMMENT] counting multi-line comment line");
                 cr.comments += 1;
                 ps.curr_line_counted = true;
             }
-            sm.set_state(STATE_CODE);
-            self.ready = false; // XXX Forget stored token
             return false;
         }
         // If the ending token is not at the end of the line remainder, we probably should
-        // not count the line a comment, and pass the remainder to StateCode instead.
+        // not count the line a comment, and pass the remainder to State::Code instead.
         ps.curr_line.replace(&line_rem[index + token.len()..]);
-        sm.set_state(STATE_CODE);
-        self.ready = false; // XXX Forget stored token
         true
     }
-}
 
-/// TODO Documentation
-#[derive(Debug)]
-struct StateCode {}
-
-impl State for StateCode {
-    #[inline]
-    fn get_state_no(&self) -> usize {
-        STATE_CODE
-    }
-
-    #[inline]
-    fn set_token(&mut self, _: &str) {}
-
-    /// TODO: Documentation
-    fn process(
+    /// Looks for the end of the string literal started by `token` in the line remainder. Lines
+    /// containing (part of) a string literal are always counted as code, even if they are
+    /// otherwise blank. Returns whether there is more processing to be done in the same line.
+    fn process_string(
         &mut self,
-        sm: &mut LOCStateMachine,
+        token: &'static str,
         ps: &mut ParsingState,
         cr: &mut CountResult,
     ) -> bool {
         rlocc_dbg_log!(
-            "[STATE_CODE][process] line ({}) = {}",
+            "[STATE_STRING][process] state = {:?}\tline ({}) = {}",
+            self.state,
             cr.total + 1,
             ps.curr_line.unwrap().trim_end()
         );
 
-        // Whitespace must have already been trimmed in ps.curr_line when
-        // populated in Worker.process_line().
-        let line_rem = ps.curr_line.unwrap();
-        if line_rem.is_empty() {
-            // Count the line as blank and move on, but remain in StateCode.
-            rlocc_dbg_log!("[STATE_CODE][process] counting line as blank");
-            cr.blank += 1;
+        if !ps.curr_line_counted {
+            rlocc_dbg_log!("[STATE_STRING][process] counting line as code");
+            cr.code += 1;
             ps.curr_line_counted = true;
-            sm.set_state(self.get_state_no());
-            return false; // move on to the next line
         }
 
-        // Find the index of the first inline comment token, if any.
-        let first_inline_index = find_inline(&line_rem, ps.curr_lang);
-        if let Some(0) = first_inline_index {
-            // If the inline comment token is in the beginning of the line, count the
-            // line as a comment, move on to the next line, but remain in StateCode.
-            rlocc_dbg_log!("[STATE_CODE][process] counting line as comment");
-            cr.comments += 1;
-            ps.curr_line_counted = true;
-            sm.set_state(self.get_state_no());
-            return false; // move on to the next line
+        let line_rem = ps.curr_line.unwrap();
+        let escape = ps.curr_lang.string_tokens.contains(&token);
+        if let Some(index) = find_string_end(line_rem, token, escape) {
+            // The string literal ends in this line, so pass the remainder to State::Code.
+            ps.curr_line.replace(&line_rem[index + token.len()..]);
+            self.state = State::Code;
+            true // keep processing the same line
+        } else {
+            // The string literal spans multiple lines, so remain in State::String.
+            false // move on to the next line
         }
+    }
+}
 
-        // Find the index of the first multiline comment start token, if any.
-        let first_multiline_start = find_multiline(&mut MultiLine::Start, &line_rem, ps.curr_lang);
-        if let Some((0, token)) = first_multiline_start {
-            // If the multiline comment token is in the beginning of the line, don't count this
-            // line yet (since we don't know where the comment ends), but change to StateMultiline,
-            // after updating the line remainder to look past the found token.
-            ps.curr_line.replace(&line_rem[token.len()..]); // update line remainder
-            sm.set_state(STATE_MULTI_LINE_COMMENT);
-            sm.states[STATE_MULTI_LINE_COMMENT]
-                .borrow_mut()
-                .set_token(token);
-            return true; // keep processing the same line
-        }
+/// Returns the ending token of the language that corresponds to the given multi-line comment
+/// starting token, i.e., the mirrored one (e.g., "*/" for "/*" or "-}" for "{-"), if any.
+/// Returns `None` for languages whose comments may be ended by any of their ending tokens, and
+/// for starting tokens that are not mirrored by any ending token (e.g., Nim's "#[" and "]#").
+fn find_multiline_end_token(start_tkn: &str, lang: &Language) -> Option<&'static str> {
+    if let "Delphi" | "HTML" | "Lua" | "Pascal" | "Perl" | "Ruby" | "XML" = lang.name {
+        return None;
+    }
+    let mirrored = start_tkn.chars().rev().map(|c| match c {
+        '(' => ')',
+        '{' => '}',
+        '<' => '>',
+        _ => c,
+    });
+    lang.multiline_comment_end_tokens
+        .iter()
+        .find(|token| token.chars().eq(mirrored.clone()))
+        .copied()
+}
 
-        // If the line hasn't been blank and doesn't start with an inline or a multiline
-        // comment, then count it as code, and figure out the next state.
-        if !ps.curr_line_counted {
-            // Don't count it twice if it has been already counted.
-            rlocc_dbg_log!("[STATE_CODE][process] counting line as code");
-            cr.code += 1;
-            ps.curr_line_counted = true;
+/// Returns whether the string literal delimiter found at `index` of `line` is actually quoted as
+/// a character literal (e.g., `'"'` or `'\"'`) in a language where `'` does not delimit strings
+/// itself (e.g., Rust, where it also starts lifetimes).
+fn is_char_literal(line: &str, index: usize, token: &str) -> bool {
+    let (before, after) = (&line[..index], &line[index + token.len()..]);
+    token.len() == 1
+        && after.starts_with('\'')
+        && (before.ends_with('\'') || before.ends_with("'\\"))
+}
+
+/// Searches `line` for the given string literal ending delimiter, skipping any occurrences of it
+/// that are escaped by a backslash if `escape` is true, and returns the index at which it was
+/// found, if any.
+fn find_string_end(line: &str, token: &str, escape: bool) -> Option<usize> {
+    // Delimiters are ASCII, so comparing bytes never matches in the middle of a multi-byte
+    // character and any index returned is a valid char boundary.
+    let (line, token) = (line.as_bytes(), token.as_bytes());
+    let mut i = 0;
+    while i < line.len() {
+        if escape && line[i] == b'\\' {
+            i += 2; // skip the escaped byte, whatever it is
+            continue;
         }
-        if first_inline_index.is_none() && first_multiline_start.is_none() {
-            // The line is pure code, so...
-            sm.set_state(STATE_CODE); // change to StateCode
-            false // and just move on to the next line
-        } else if first_inline_index.is_none() && first_multiline_start.is_some() {
-            // The line doesn't contain an inline comment token, and it contains a starting
-            // multiline comment token. Therefore, line remainder is updated, state is changed
-            // to StateMultiLineComment, and we have to keep processing the same line.
-            let (index, token) = first_multiline_start.unwrap();
-            ////if let Some((index, token)) = first_multiline_start {
-            ////    ps.curr_line.replace(&line_rem[index + token.len()..]); // update line remainder
-            ////}
-            ps.curr_line.replace(&line_rem[index + token.len()..]); // update line remainder
-            sm.set_state(STATE_MULTI_LINE_COMMENT); // change state
-            sm.states[STATE_MULTI_LINE_COMMENT]
-                .borrow_mut()
-                .set_token(token);
-            true // keep processing the same line
-        } else if first_inline_index.is_some() && first_multiline_start.is_none() {
-            // The line starts with code and ends with some inline comment, so...
-            sm.set_state(STATE_CODE); // change to StateCode
-            false // move on to the next line
-        } else if first_inline_index.is_some() && first_multiline_start.is_some() {
-            // The line contains both a multiline comment starting token and an inline
-            // comment token. If the latter precedes the first, it "invalidates" it.
-            let first_inline_index = first_inline_index.unwrap();
-            let (multi_start_index, multi_start_token) = first_multiline_start.unwrap();
-            if multi_start_index < first_inline_index {
-                // A multiline comment starts in this line (at some point, after the code), so...
-                ps.curr_line
-                    .replace(&line_rem[multi_start_index + multi_start_token.len()..]); // update line remainder
-                sm.set_state(STATE_MULTI_LINE_COMMENT); // change state
-                sm.states[STATE_MULTI_LINE_COMMENT]
-                    .borrow_mut()
-                    .set_token(multi_start_token);
-                true // keep processing the same line
-            } else {
-                // The line starts with code and ends with some inline comment, so...
-                sm.set_state(STATE_CODE); // change to StateCode
-                false // move on to the next line
-            }
-        } else {
-            unreachable!();
+        if line[i..].starts_with(token) {
+            return Some(i);
         }
+        i += 1;
     }
+    None
 }
 
 #[cfg(test)]
@@ -466,35 +414,163 @@ mod tests {
     impl A<'_> {
         #[inline]
         fn new(lang: &'static Language) -> Self {
-            let mut sm = LOCStateMachine::new();
-            sm.set_state(STATE_CODE);
             A {
-                sm,
+                sm: LOCStateMachine::new(),
                 ps: ParsingState::new(lang),
                 cr: CountResult::new(lang.name),
             }
         }
     }
 
-    //#[test]
+    impl<'a> A<'a> {
+        /// Feed the given lines to the state machine, the same way `Worker::process_line` does.
+        fn count(&mut self, lines: &[&'a str]) -> CountResult {
+            for line in lines {
+                self.ps = ParsingState::new(self.ps.curr_lang);
+                self.ps.curr_line = Some(line.trim_start());
+                self.sm.process(&mut self.ps, &mut self.cr);
+                self.cr.total += 1;
+                assert_eq!(
+                    self.cr.total,
+                    self.cr.code + self.cr.comments + self.cr.blank
+                );
+            }
+            self.cr
+        }
+    }
+
+    #[test]
     fn state_initial_empty_line() {
         let mut a = A::new(EXT_TO_LANG.get(&"rs").unwrap());
         //eprintln!("{:#?}", a);
         let lines = vec!["", "    "];
         for line in &lines {
+            a.ps = ParsingState::new(a.ps.curr_lang);
             a.ps.curr_line = Some(line);
             //eprintln!("ps = {:?}", a.ps);
             //eprintln!("sm = {:?}", a.sm);
             //eprintln!("ps.curr_line = {:?}", a.ps.curr_line);
-            if let Some(sinit) = a.sm.state.take() {
-                assert_eq!(
-                    false,
-                    sinit.borrow_mut().process(&mut a.sm, &mut a.ps, &mut a.cr)
-                );
-            }
-            a.sm.set_state(STATE_CODE);
+            let matcher = languages::token_matcher(a.ps.curr_lang);
+            assert!(!a.sm.process_code(&matcher, &mut a.ps, &mut a.cr));
+            assert_eq!(a.sm.state, State::Code);
         }
         assert_eq!(lines.len(), a.cr.blank);
         //eprintln!("cr = {:?}", a.cr);
     }
+
+    #[test]
+    fn string_hides_comment_tokens() {
+        let mut a = A::new(EXT_TO_LANG.get(&"rs").unwrap());
+        let cr = a.count(&[
+            r#"let glob = "src/*.rs";"#,
+            "let x = 42;",
+            r#"let s = "// not a comment";"#,
+        ]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (3, 0, 0));
+    }
+
+    #[test]
+    fn string_escaped_delimiter() {
+        let mut a = A::new(EXT_TO_LANG.get(&"c").unwrap());
+        let cr = a.count(&[
+            r#"char *s = "\" /* \\"; // trailing"#,
+            r#"char c = '\''; /* comment"#,
+            "   still a comment */",
+        ]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (2, 1, 0));
+    }
+
+    #[test]
+    fn string_raw_delimiter() {
+        let mut a = A::new(EXT_TO_LANG.get(&"sql").unwrap());
+        let cr = a.count(&[r#"SELECT 'C:\' FROM t; /* a"#, "b */", "-- c"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 2, 0));
+    }
+
+    #[test]
+    fn string_multiline() {
+        let mut a = A::new(EXT_TO_LANG.get(&"js").unwrap());
+        let cr = a.count(&[
+            "const s = `",
+            "/* not a comment",
+            "",
+            "`; // comment",
+            "// comment",
+        ]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (4, 1, 0));
+    }
+
+    #[test]
+    fn string_after_multiline_comment() {
+        let mut a = A::new(EXT_TO_LANG.get(&"go").unwrap());
+        let cr = a.count(&[r#"/* a */ // b"#, r#"/* a */ s := "*/""#, r#"s := "a""#, ""]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (2, 1, 1));
+    }
+
+    #[test]
+    fn python_docstring_is_not_a_string() {
+        let mut a = A::new(EXT_TO_LANG.get(&"py").unwrap());
+        let cr = a.count(&[r#"""""#, "doc", r#"""""#, r##"s = "# not a comment""##]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 3, 0));
+    }
+
+    #[test]
+    fn multiline_preferred_over_inline_comment() {
+        let mut a = A::new(EXT_TO_LANG.get(&"lua").unwrap());
+        let cr = a.count(&["--[[", "x = 1", "]]", "x = 1"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 3, 0));
+    }
+
+    #[test]
+    fn nested_comments() {
+        let mut a = A::new(EXT_TO_LANG.get(&"rs").unwrap());
+        let cr = a.count(&["/* a /* b */", "still a comment", "*/ x();", "/* /* */ */"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 3, 0));
+
+        let mut a = A::new(EXT_TO_LANG.get(&"hs").unwrap());
+        let cr = a.count(&["{- {-", "-} -}", "main = x {- {- -} -}"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 2, 0));
+    }
+
+    #[test]
+    fn nested_comments_only_for_nesting_tokens() {
+        let mut a = A::new(EXT_TO_LANG.get(&"d").unwrap());
+        let cr = a.count(&["/* /* */", "x();", "/+ /+ +/", "+/", "x();"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (2, 3, 0));
+
+        let mut a = A::new(EXT_TO_LANG.get(&"c").unwrap());
+        let cr = a.count(&["/* /* */", "x();"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 1, 0));
+    }
+
+    #[test]
+    fn char_literal_is_not_a_string() {
+        let mut a = A::new(EXT_TO_LANG.get(&"rs").unwrap());
+        let cr = a.count(&[
+            r#"let q = '"'; /* a"#,
+            "comment */",
+            r#"let e = '\"'; let s = "'"; // trailing"#,
+            "// a comment",
+            "fn f<'a>(s: &'a str) {}",
+        ]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (3, 2, 0));
+    }
+
+    #[test]
+    fn elixir_comments() {
+        let mut a = A::new(EXT_TO_LANG.get(&"ex").unwrap());
+        let cr = a.count(&[
+            "# a comment",
+            "%{a: 1}",
+            r##"s = "# not a comment" # trailing"##,
+        ]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (2, 1, 0));
+    }
+
+    #[test]
+    fn matlab_block_comments() {
+        let mut a = A::new(EXT_TO_LANG.get(&"m").unwrap());
+        let cr = a.count(&["%{", "a comment", "%}", "x = 1;", "% a comment"]);
+        assert_eq!((cr.code, cr.comments, cr.blank), (1, 4, 0));
+    }
 }
@@ -0,0 +1,507 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel as chan;
+
+use super::config::Config;
+use super::ignores::{Globs, Ignores};
+use super::languages::{self, Language};
+use super::skipped::{SkipReason, SkipReport};
+
+/// A unit of work found while walking the given paths.
+#[derive(Debug)]
+enum Job {
+    /// A file to be counted.
+    File(PathBuf),
+    /// A directory to be walked.
+    Dir(Dir),
+}
+
+/// A directory to be walked, along with the globs of the given path it has been found under and
+/// the ignore files of its parents.
+#[derive(Debug)]
+struct Dir {
+    path: PathBuf,
+    globs: Arc<Globs>,
+    ignores: Option<Arc<Ignores>>,
+}
+
+/// The work queue shared by the Coordinator and all Workers. Workers walking a directory push
+/// its files and subdirectories back to it, so that the walk itself is carried out in parallel.
+/// The queue is bounded; jobs that do not fit are done in place by the Worker that found them,
+/// so that the walk cannot run ahead of the counting.
+#[derive(Debug)]
+struct Queue {
+    /// Sends `None` to each Worker once all work is done.
+    tx: chan::Sender<Option<Job>>,
+    rx: chan::Receiver<Option<Job>>,
+    /// The number of jobs pushed but not yet done.
+    pending: AtomicUsize,
+    /// The largest number of jobs pending at once so far.
+    #[cfg(test)]
+    peak: AtomicUsize,
+    /// Directories (and, when de-duplicating, files) walked so far.
+    visited: Mutex<HashSet<FileId>>,
+}
+
+/// A handle to the work queue, used both to seed it with the given paths and to walk the
+/// directories popped from it.
+#[derive(Debug, Clone)]
+pub struct Walker<'w> {
+    config: &'w Config,
+    queue: Arc<Queue>,
+}
+
+impl<'w> Walker<'w> {
+    /// Returns a handle to a new, empty work queue.
+    pub fn new(config: &'w Config) -> Self {
+        let (tx, rx) = chan::bounded(config.queue_capacity());
+        Walker {
+            config,
+            queue: Arc::new(Queue {
+                tx,
+                rx,
+                // Held by the Coordinator until it is done walking the given paths, so that the
+                // Workers cannot run out of work before it even starts.
+                pending: AtomicUsize::new(1),
+                #[cfg(test)]
+                peak: AtomicUsize::new(1),
+                visited: Mutex::new(HashSet::new()),
+            }),
+        }
+    }
+
+    /// Pushes a job to the work queue, blocking while it is full. Only the Coordinator may block,
+    /// since the queue can only make room for more jobs as long as the Workers keep popping them.
+    #[inline]
+    fn push(&self, job: Job) {
+        self.queue.pending.fetch_add(1, Ordering::SeqCst);
+        self.queue.tx.send(Some(job)).unwrap();
+        self.pushed();
+    }
+
+    /// Pushes a job to the work queue, unless it is full, in which case the job is handed back.
+    #[inline]
+    fn try_push(&self, job: Job) -> Result<(), Job> {
+        // Counted beforehand, so that no pending jobs can be seen before this one is done; the
+        // job being done by the caller keeps the count from dropping to zero in the meantime.
+        self.queue.pending.fetch_add(1, Ordering::SeqCst);
+        match self.queue.tx.try_send(Some(job)) {
+            Ok(()) => {
+                self.pushed();
+                Ok(())
+            }
+            Err(chan::TrySendError::Full(job)) => {
+                self.queue.pending.fetch_sub(1, Ordering::SeqCst);
+                Err(job.unwrap())
+            }
+            Err(chan::TrySendError::Disconnected(_)) => unreachable!(),
+        }
+    }
+
+    /// Keeps track of the largest number of jobs pending at once, once a job has been pushed.
+    #[inline]
+    fn pushed(&self) {
+        #[cfg(test)]
+        self.queue
+            .peak
+            .fetch_max(self.queue.pending.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+
+    /// Blocks until a job is available, or returns `None` once all work is done.
+    #[inline]
+    fn pop(&self) -> Option<Job> {
+        self.queue.rx.recv().ok().flatten()
+    }
+
+    /// Marks a job popped from the work queue as done. Once no jobs are pending anymore, all
+    /// Workers are told to stop.
+    #[inline]
+    fn done(&self) {
+        if self.queue.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            rlocc_dbg_log!("[Walker][done] No more work to do!");
+            for _ in 0..self.config.num_threads {
+                self.queue.tx.send(None).unwrap();
+            }
+        }
+    }
+
+    /// Seeds the work queue with the filesystem paths given. Paths that cannot be read are
+    /// recorded in `skipped`.
+    pub fn walk_paths(&self, skipped: &mut SkipReport) {
+        let ignores = if self.config.no_ignore {
+            None
+        } else {
+            Some(Ignores::global())
+        };
+        for path in self.config.paths.iter() {
+            if path.is_file() {
+                if self.is_lang_included(path, skipped) && !self.is_duplicate(path, skipped) {
+                    rlocc_dbg_log!("[Walker][walk_paths] Pushing {:?}...", path);
+                    self.push(Job::File(path.to_owned()));
+                }
+            } else if path.is_dir() && !languages::is_vcs(&path) {
+                rlocc_dbg_log!("[Walker][walk_paths] Pushing {:?}...", path);
+                self.push(Job::Dir(Dir {
+                    path: path.to_owned(),
+                    globs: Arc::new(self.config.globs(path)),
+                    ignores: ignores.as_ref().map(|ignores| ignores.add_parents(path)),
+                }));
+            } else if let Err(err) = fs::metadata(path) {
+                rlocc_dbg_log!("[Walker][walk_paths] Skipping {:?}: {}", path, err);
+                skipped.add(
+                    path,
+                    SkipReason::Io,
+                    &err,
+                    self.config.keeps_skipped_paths(),
+                );
+            } else {
+                rlocc_dbg_log!("[Walker][walk_paths] Skipping non-regular file {:?}.", path);
+            }
+        }
+        self.done();
+    }
+
+    /// Carries out the jobs popped from the work queue until all work is done, handing the files
+    /// to be counted to `count` and walking the directories. Entries that cannot be read are
+    /// recorded in `skipped`.
+    pub fn run(&self, skipped: &mut SkipReport, count: &mut dyn FnMut(PathBuf)) {
+        while let Some(job) = self.pop() {
+            self.run_job(job, skipped, count);
+            self.done();
+        }
+    }
+
+    #[inline]
+    fn run_job(&self, job: Job, skipped: &mut SkipReport, count: &mut dyn FnMut(PathBuf)) {
+        match job {
+            Job::File(path) => count(path),
+            Job::Dir(dir) => {
+                rlocc_dbg_log!("[Walker][run_job] Walking {:?}...", dir);
+                self.walk_dir(dir, skipped, count);
+            }
+        }
+    }
+
+    /// Returns whether the given file should be counted as far as the languages to be counted
+    /// are concerned. Files whose language can be told by their path alone are filtered here, so
+    /// that they are never even opened, and recorded in `skipped`.
+    fn is_lang_included(&self, path: &Path, skipped: &mut SkipReport) -> bool {
+        if !self.config.filters_langs() {
+            return true;
+        }
+        let lang = match self.config.extension_lang(&path) {
+            Some(name) => languages::find_language(name),
+            None => match languages::guess_language(&path) {
+                Ok((ext, lang)) if !languages::is_ambiguous(ext) => Some(lang),
+                _ => None,
+            },
+        };
+        match lang {
+            Some(lang) if !self.config.is_lang_included(lang.name) => {
+                rlocc_dbg_log!("[Walker] Skipping {:?} in {}.", path, lang.name);
+                let err = not_included(lang);
+                skipped.add(
+                    path,
+                    SkipReason::ExcludedLanguage,
+                    &err,
+                    self.config.keeps_skipped_paths(),
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns whether the given file is the same as (e.g., a hard link to) one that has already
+    /// been pushed to the work queue, in which case it is recorded in `skipped`. Files are only
+    /// told apart this way if de-duplication has been enabled.
+    fn is_duplicate(&self, path: &Path, skipped: &mut SkipReport) -> bool {
+        if !self.config.dedup {
+            return false;
+        }
+        match file_id(path) {
+            Ok(id) if !self.queue.visited.lock().unwrap().insert(id) => {
+                rlocc_dbg_log!("[Walker] Skipping duplicate {:?}.", path);
+                let err = io::Error::other("Same file as another path");
+                skipped.add(
+                    path,
+                    SkipReason::Duplicate,
+                    &err,
+                    self.config.keeps_skipped_paths(),
+                );
+                true
+            }
+            // Any errors are left for the workers to report.
+            _ => false,
+        }
+    }
+
+    /// Walks the entries of the given directory, pushing its files and subdirectories to the
+    /// work queue. Whenever the queue is full, the entry is dealt with right away instead (i.e.,
+    /// the file is handed to `count` or the subdirectory is walked), so that the queue stays
+    /// bounded while the other Workers keep popping from it. Entries matching the exclude globs
+    /// are never read. The ignores of the directory, if any, are extended with the ignore files
+    /// found in it before being used for its entries. Directories (and, when de-duplicating,
+    /// files) already visited are not walked again, so that symbolic links cannot lead to cycles.
+    /// Entries that cannot be read are recorded in `skipped`.
+    fn walk_dir(&self, dir: Dir, skipped: &mut SkipReport, count: &mut dyn FnMut(PathBuf)) {
+        let Dir {
+            path,
+            globs,
+            ignores,
+        } = dir;
+        match file_id(&path) {
+            Ok(id) if !self.queue.visited.lock().unwrap().insert(id) => {
+                rlocc_dbg_log!("[Walker][walk_dir] Skipping visited {:?}.", path);
+                return;
+            }
+            Ok(_) => (),
+            Err(err) => {
+                rlocc_dbg_log!("[Walker][walk_dir] Skipping {:?}: {}", path, err);
+                skipped.add(
+                    &path,
+                    SkipReason::Io,
+                    &err,
+                    self.config.keeps_skipped_paths(),
+                );
+                return;
+            }
+        }
+        let ignores = ignores.map(|ignores| ignores.add_dir(&path));
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                rlocc_dbg_log!("[Walker][walk_dir] Skipping {:?}: {}", path, err);
+                skipped.add(
+                    &path,
+                    SkipReason::Io,
+                    &err,
+                    self.config.keeps_skipped_paths(),
+                );
+                return;
+            }
+        };
+        for direntry in entries {
+            let (direntry, file_type) = match direntry.and_then(|e| Ok((e.path(), e.file_type()?)))
+            {
+                Ok(entry) => entry,
+                Err(err) => {
+                    rlocc_dbg_log!("[Walker][walk_dir] Skipping entry of {:?}: {}", path, err);
+                    skipped.add(
+                        &path,
+                        SkipReason::Io,
+                        &err,
+                        self.config.keeps_skipped_paths(),
+                    );
+                    continue;
+                }
+            };
+            let file_type = if !file_type.is_symlink() {
+                file_type
+            } else if !self.config.follow_symlinks {
+                rlocc_dbg_log!("[Walker][walk_dir] Skipping symlink {:?}.", direntry);
+                continue;
+            } else {
+                match fs::metadata(&direntry) {
+                    Ok(metadata) => metadata.file_type(),
+                    Err(err) => {
+                        rlocc_dbg_log!("[Walker][walk_dir] Skipping {:?}: {}", direntry, err);
+                        skipped.add(
+                            &direntry,
+                            SkipReason::Io,
+                            &err,
+                            self.config.keeps_skipped_paths(),
+                        );
+                        continue;
+                    }
+                }
+            };
+            let is_dir = file_type.is_dir();
+            if globs.is_excluded(&direntry, is_dir) {
+                rlocc_dbg_log!("[Walker][walk_dir] Skipping excluded {:?}.", direntry);
+            } else if ignores
+                .as_ref()
+                .is_some_and(|ignores| ignores.is_ignored(&direntry, is_dir))
+            {
+                rlocc_dbg_log!("[Walker][walk_dir] Skipping ignored {:?}.", direntry);
+            } else if file_type.is_file() {
+                if !globs.is_included(&direntry) {
+                    rlocc_dbg_log!("[Walker][walk_dir] Skipping {:?}, not included.", direntry);
+                } else if self.is_lang_included(&direntry, skipped)
+                    && !self.is_duplicate(&direntry, skipped)
+                {
+                    rlocc_dbg_log!("[Walker][walk_dir] Pushing {:?}...", direntry);
+                    if let Err(job) = self.try_push(Job::File(direntry)) {
+                        self.run_job(job, skipped, count);
+                    }
+                }
+            } else if is_dir && !languages::is_vcs(&direntry) {
+                rlocc_dbg_log!("[Walker][walk_dir] Pushing {:?}...", direntry);
+                let job = Job::Dir(Dir {
+                    path: direntry,
+                    globs: Arc::clone(&globs),
+                    ignores: ignores.clone(),
+                });
+                if let Err(job) = self.try_push(job) {
+                    self.run_job(job, skipped, count);
+                }
+            } else {
+                rlocc_dbg_log!(
+                    "[Walker][walk_dir] Skipping non-regular file {:?}.",
+                    direntry
+                );
+            }
+        }
+    }
+}
+
+/// Returns the error of files skipped because of their language not being included.
+#[inline]
+pub fn not_included(lang: &Language) -> io::Error {
+    io::Error::other(format!("Language '{}' is not included", lang.name))
+}
+
+/// Identifies a file or directory regardless of the path it is reached through (e.g., a symbolic
+/// or hard link).
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+/// Returns the device and inode numbers of the given file or directory.
+#[cfg(unix)]
+fn file_id(path: &Path) -> io::Result<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+/// Returns the canonical path of the given file or directory.
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> io::Result<FileId> {
+    fs::canonicalize(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_utils::thread;
+    use std::collections::HashSet;
+    use std::sync::{Condvar, Mutex};
+    use std::time::Duration;
+
+    /// Walks the given path with the given number of Workers and capacity of the work queue,
+    /// calling `count` on each file handed to a Worker, and returning the files handed to each
+    /// Worker and the largest number of jobs pending at once.
+    fn walk(
+        path: &Path,
+        num_threads: usize,
+        queue_capacity: usize,
+        count: &(dyn Fn(&Path) + Sync),
+    ) -> (Vec<Vec<PathBuf>>, usize) {
+        let config = Config {
+            paths: vec![path.to_owned()],
+            num_threads,
+            queue_capacity: Some(queue_capacity),
+            ..Default::default()
+        };
+        let walker = Walker::new(&config);
+        let files = thread::scope(|s| {
+            let workers: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    let walker = walker.clone();
+                    s.spawn(move |_| {
+                        let mut files = Vec::new();
+                        walker.run(&mut SkipReport::default(), &mut |path| {
+                            count(&path);
+                            files.push(path);
+                        });
+                        files
+                    })
+                })
+                .collect();
+            walker.walk_paths(&mut SkipReport::default());
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        })
+        .unwrap();
+        let peak = walker.queue.peak.load(Ordering::SeqCst);
+        (files, peak)
+    }
+
+    #[test]
+    fn bounded_queue() {
+        let root = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            let dir = root.path().join(format!("d{}/e{}", i % 4, i % 3));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("f{}.rs", i)), "fn main() {}\n").unwrap();
+        }
+
+        let (num_threads, queue_capacity) = (4, 2);
+        // Counting a file takes much longer than walking past it.
+        let count = |_: &Path| std::thread::sleep(Duration::from_millis(1));
+        let (files, peak) = walk(root.path(), num_threads, queue_capacity, &count);
+        let mut files: Vec<_> = files.into_iter().flatten().collect();
+        files.sort();
+        files.dedup();
+        assert_eq!(files.len(), 200);
+        // Those queued, those being done by each Worker and the paths held by the Coordinator.
+        assert!(peak <= queue_capacity + num_threads + 1, "peak: {}", peak);
+    }
+
+    #[test]
+    fn shared_work() {
+        let root = tempfile::tempdir().unwrap();
+        for i in 0..100 {
+            fs::write(root.path().join(format!("f{}.rs", i)), "fn main() {}\n").unwrap();
+        }
+
+        // The files of a directory larger than the queue are not all left to the Worker that
+        // walks it: none of the Workers gets past its first file until another Worker has been
+        // handed one too, which would never happen otherwise.
+        let workers = (Mutex::new(HashSet::new()), Condvar::new());
+        let count = |_: &Path| {
+            let mut ids = workers.0.lock().unwrap();
+            ids.insert(std::thread::current().id());
+            workers.1.notify_all();
+            let (ids, timeout) = workers
+                .1
+                .wait_timeout_while(ids, Duration::from_secs(10), |ids| ids.len() < 2)
+                .unwrap();
+            assert!(!timeout.timed_out(), "workers: {}", ids.len());
+        };
+        let (files, _) = walk(root.path(), 4, 8, &count);
+        assert_eq!(files.iter().map(Vec::len).sum::<usize>(), 100);
+        let workers = files.iter().filter(|files| !files.is_empty()).count();
+        assert!(
+            workers > 1,
+            "files per worker: {:?}",
+            files.iter().map(Vec::len).collect::<Vec<_>>()
+        );
+    }
+}
@@ -17,29 +17,73 @@
 
 use std::env;
 use std::io::{self, BufWriter, Write};
+use std::process;
 
-use rlocc::locc::{self, Config, LOCCount};
+use rlocc::locc::{self, Config, ConfigError, LOCCount, OutputFormat, SkipReason};
 
 fn main() -> io::Result<()> {
-    let args = env::args().skip(1).collect::<Vec<_>>();
-    let config = Config::new(args.into_iter(), 0).unwrap_or_default();
+    let mut config = match Config::new(env::args().skip(1), 0) {
+        Ok(config) => config,
+        Err(err @ ConfigError::Help) | Err(err @ ConfigError::Version) => {
+            println!("{}", err);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("rlocc: {}\n\n{}", err, locc::USAGE);
+            process::exit(2);
+        }
+    };
     #[cfg(debug_assertions)]
     eprintln!("{:#?}", config);
 
+    #[cfg(feature = "serde")]
+    if let Err(err) = locc::register_lang_files(&config.lang_files) {
+        eprintln!("rlocc: {}", err);
+        process::exit(2);
+    }
+    if let Err(err) = config.resolve_langs() {
+        eprintln!("rlocc: {}", err);
+        process::exit(2);
+    }
+
     let ret = locc::count_all(&config)?;
-    print_results(&ret)?;
+    print_results(&config, &ret)?;
+    if config.strict && ret.skipped().count(SkipReason::Io) > 0 {
+        process::exit(1);
+    }
     Ok(())
 }
 
 #[inline(always)]
-fn print_results(loccount: &LOCCount) -> io::Result<()> {
+fn print_results(config: &Config, loccount: &LOCCount) -> io::Result<()> {
     const STDOUT_BUF_SIZE: usize = 1 << 13;
 
     // XXX There should be some small gains by buffering IO to stdout like this...
     //     I.e., heap allocation for the buffer vs multiple write(2) syscalls.
     let stdout = io::stdout();
     let mut bw = BufWriter::with_capacity(STDOUT_BUF_SIZE, stdout.lock());
-    writeln!(bw, "{}", loccount)
+    match config.output_format {
+        OutputFormat::Table => {
+            let table = loccount
+                .table(config.sort)
+                .documentation(&config.documentation_langs);
+            writeln!(bw, "{}", table)?;
+            if !loccount.skipped().is_empty() {
+                bw.flush()?;
+                if config.keeps_skipped_paths() {
+                    eprintln!("{:#}", loccount.skipped());
+                } else {
+                    eprintln!("{}", loccount.skipped());
+                }
+            }
+            Ok(())
+        }
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => {
+            loccount.write_json(&mut bw, &config.documentation_langs)?;
+            writeln!(bw)
+        }
+    }
 }
 
 #[cfg(test)]
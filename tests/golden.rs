@@ -0,0 +1,113 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Counts the snippets in `tests/golden/corpus/` and compares the results against the counts
+//! expected for each of them in `tests/golden/expected.txt`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use rlocc::locc::{count_all, languages, Config};
+
+/// The blank lines, comments and code expected of a file, along with its language.
+type Counts = (usize, usize, usize, String);
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Parses `expected.txt`, whose lines hold the name of each file, the counts expected of it and
+/// its language, separated by whitespace; lines starting with `#` are comments.
+fn expected() -> BTreeMap<String, Counts> {
+    let expected = fs::read_to_string(golden_dir().join("expected.txt")).unwrap();
+    expected
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert!(fields.len() >= 5, "malformed line: {:?}", line);
+            let count = |i: usize| fields[i].parse::<usize>().unwrap();
+            let lang = fields[4..].join(" ");
+            (fields[0].to_owned(), (count(1), count(2), count(3), lang))
+        })
+        .collect()
+}
+
+/// Counts the corpus, file by file.
+fn actual() -> BTreeMap<String, Counts> {
+    let corpus = golden_dir().join("corpus");
+    let config = Config {
+        paths: vec![corpus.clone()],
+        by_file: true,
+        // The corpus has a `.gitignore` snippet of its own.
+        no_ignore: true,
+        ..Default::default()
+    };
+    let loccount = count_all(&config).unwrap();
+    assert!(loccount.skipped().is_empty(), "{:?}", loccount.skipped());
+    loccount
+        .files()
+        .iter()
+        .map(|file| {
+            let cr = &file.result;
+            assert_eq!(cr.total, cr.blank + cr.comments + cr.code, "{:?}", file);
+            let name = file.path.strip_prefix(&corpus).unwrap();
+            let counts = (cr.blank, cr.comments, cr.code, cr.lang().to_owned());
+            (name.to_string_lossy().into_owned(), counts)
+        })
+        .collect()
+}
+
+fn format(name: &str, counts: Option<&Counts>) -> String {
+    match counts {
+        Some((blank, comments, code, lang)) => format!(
+            "{:<16}  {:>5}  {:>8}  {:>4}  {}",
+            name, blank, comments, code, lang
+        ),
+        None => format!("{:<16}  (missing)", name),
+    }
+}
+
+#[test]
+fn golden() {
+    let (expected, actual) = (expected(), actual());
+    let names: BTreeSet<&String> = expected.keys().chain(actual.keys()).collect();
+    let mut diff = String::new();
+    for name in names {
+        let (expected, actual) = (expected.get(name), actual.get(name));
+        if expected != actual {
+            diff.push_str(&format!("- {}\n", format(name, expected)));
+            diff.push_str(&format!("+ {}\n", format(name, actual)));
+        }
+    }
+    assert!(
+        diff.is_empty(),
+        "counts differ from expected.txt:\n{}",
+        diff
+    );
+}
+
+#[test]
+fn golden_covers_all_languages() {
+    let covered: BTreeSet<String> = expected().into_values().map(|(.., lang)| lang).collect();
+    let missing: Vec<&str> = languages()
+        .map(|lang| lang.name)
+        .filter(|name| !covered.contains(*name))
+        .collect();
+    assert!(missing.is_empty(), "no golden files for {:?}", missing);
+}
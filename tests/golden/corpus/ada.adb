@@ -0,0 +1,9 @@
+-- Greets.
+with Ada.Text_IO; use Ada.Text_IO;
+
+procedure Hello is
+   Msg : constant String := "-- not a comment"; -- trailing
+begin
+   Put_Line (Msg);  -- prints it
+   Put_Line ("say ""hi""");
+end Hello;
@@ -0,0 +1,11 @@
+; Prints a message.
+section .data
+    msg db "hello; world", 10   ; trailing
+
+section .text
+global _start
+_start:
+    mov rax, 1      ; write
+    ; exit
+    mov rax, 60
+    syscall
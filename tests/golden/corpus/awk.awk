@@ -0,0 +1,9 @@
+#!/usr/bin/awk -f
+# Counts the fields.
+BEGIN { FS = "#" }   # split on hashes
+
+{
+    n += NF  # running total
+}
+
+END { print "total: " n }
@@ -0,0 +1,7 @@
+@echo off
+REM Says hello.
+:: Also a comment.
+set MSG="REM not a comment"
+echo %MSG%
+
+echo done & REM trailing
@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+/*
+ * Prints a greeting.
+ */
+int main(void) {
+    const char *s = "/* not a comment */"; // trailing
+    char c = '"';  /* a quote */
+
+    /* comment */ printf("%s %c\n", s, c);
+    return 0; /* a comment
+                 spanning lines */
+}
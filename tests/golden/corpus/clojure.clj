@@ -0,0 +1,7 @@
+;; Greets.
+(ns hello.core)
+
+(defn greet
+  "Returns a greeting; not a comment."
+  [name]
+  (str "Hello, " name)) ; trailing
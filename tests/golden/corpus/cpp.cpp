@@ -0,0 +1,12 @@
+// A tiny class.
+#include <string>
+
+class Greeter {
+  public:
+    std::string greet() const { return "// hi"; } // trailing
+    /* inline block */ int n = 0;
+};
+
+/*
+ */
+int main() { return Greeter().greet().empty(); }
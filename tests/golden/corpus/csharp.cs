@@ -0,0 +1,12 @@
+/// <summary>Greets.</summary>
+class Program
+{
+    /* entry point */
+    static void Main()
+    {
+        var s = "/* not a comment"; // trailing
+        char q = '\'';
+
+        System.Console.WriteLine(s + q);
+    }
+}
@@ -0,0 +1,10 @@
+// Greets.
+void main() {
+  /* Outer /* inner */ still a comment */
+  var s = 'it\'s /* not a comment */';
+  print(s); // trailing
+
+  /*
+   * Doc.
+   */
+}
@@ -0,0 +1,7 @@
+# A package.
+{ pkgs ? import <nixpkgs> {} }:
+
+/* A block comment. */
+pkgs.hello.overrideAttrs (old: {
+  name = "hello-# not a comment"; # trailing
+})
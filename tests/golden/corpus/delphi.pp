@@ -0,0 +1,10 @@
+// Greets.
+program Hello;
+
+{ A brace comment }
+(* A paren
+   comment *)
+begin
+  WriteLn('{ not a comment }'); // trailing
+  WriteLn('it''s');
+end.
@@ -0,0 +1,13 @@
+-- Greets.
+class
+    HELLO
+
+create
+    make
+
+feature
+    make
+        do
+            print ("-- not a comment%N") -- trailing
+        end
+end
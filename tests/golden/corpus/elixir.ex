@@ -0,0 +1,7 @@
+# Greets.
+defmodule Hello do
+  # A comment.
+  def greet(name), do: "Hello, # not a comment #{name}" # trailing
+
+  def hello, do: greet('world')
+end
@@ -0,0 +1,10 @@
+module Main exposing (main)
+
+{-| Greets.
+    {- nested -}
+-}
+import Html exposing (text)
+
+-- A comment.
+main =
+    text "{- not a comment -}" -- trailing
@@ -0,0 +1,6 @@
+%% Greets.
+-module(hello).
+-export([greet/0]).
+
+greet() ->
+    io:format("100% not a comment~n"). % trailing
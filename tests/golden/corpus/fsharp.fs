@@ -0,0 +1,8 @@
+// Greets.
+module Hello
+
+(* Outer (* inner *) comment *)
+let greet name = sprintf "(* not a comment *) %s" name // trailing
+
+[<EntryPoint>]
+let main _ = printfn "%s" (greet "world"); 0
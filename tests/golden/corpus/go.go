@@ -0,0 +1,11 @@
+// Package main greets.
+package main
+
+import "fmt"
+
+/* A block comment. */
+func main() {
+	s := `raw // not a comment
+/* still raw */`
+	fmt.Println(s, '"') // trailing
+}
@@ -0,0 +1,7 @@
+-- | Greets.
+module Main where
+
+{- A block comment
+   {- nested -} still a comment -}
+main :: IO ()
+main = putStrLn "-- not a comment" -- trailing
@@ -0,0 +1,8 @@
+#ifndef GREETER_H
+#define GREETER_H
+
+/* Greets. */
+const char *greet(void); // declared
+
+#define URL "http://example.com" /* not // a comment */
+#endif
@@ -0,0 +1,11 @@
+/**
+ * Greets.
+ */
+public class Hello {
+    public static void main(String[] args) {
+        String s = "/* not a comment */ // nor this";
+        char c = '\\'; // a backslash
+
+        System.out.println(s + c); /* trailing */
+    }
+}
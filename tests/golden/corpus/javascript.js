@@ -0,0 +1,6 @@
+// Greets.
+const name = 'world';
+const s = `multi-line // not a comment
+/* still a template */ ${name}`;
+
+/* A comment */ console.log(s); // trailing
@@ -0,0 +1,7 @@
+# Greets.
+#= A block comment
+   #= nested =#
+=#
+function greet(name)
+    "Hello, #= not a comment =# $name" # trailing
+end
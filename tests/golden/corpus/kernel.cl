@@ -0,0 +1,8 @@
+// Adds two vectors.
+__kernel void add(__global const float *a,
+                  __global const float *b,
+                  __global float *c) {
+    /* one work item per element */
+    int i = get_global_id(0);
+    c[i] = a[i] + b[i]; // trailing
+}
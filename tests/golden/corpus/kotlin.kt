@@ -0,0 +1,6 @@
+// Greets.
+fun main() {
+    /* Outer /* inner */ comment */
+    val s = "/* not a comment */" // trailing
+    println(s)
+}
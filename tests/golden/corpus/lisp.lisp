@@ -0,0 +1,5 @@
+;;;; Greets.
+(defun greet (name)
+  #| A block comment
+     #| nested |# |#
+  (format t "Hello, ~a; not a comment~%" name)) ; trailing
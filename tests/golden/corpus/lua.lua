@@ -0,0 +1,6 @@
+-- Greets.
+--[[ A block
+     comment ]]
+local s = "--[[ not a comment ]]" -- trailing
+
+--[[ a comment ]] print(s)
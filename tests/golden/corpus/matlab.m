@@ -0,0 +1,7 @@
+% Greets.
+function greet(name)
+    %{
+    A block comment.
+    %}
+    fprintf("Hello, %s\n", name); % trailing
+end
@@ -0,0 +1,7 @@
+# Greets.
+#[ A block comment
+   #[ nested ]# ]#
+proc greet(name: string): string =
+  "Hello, #[ not a comment ]# " & name # trailing
+
+echo greet("world")
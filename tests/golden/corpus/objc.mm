@@ -0,0 +1,10 @@
+// Greets.
+#import <Foundation/Foundation.h>
+
+@interface Greeter : NSObject
+- (NSString *)greet; /* trailing */
+@end
+
+@implementation Greeter
+- (NSString *)greet { return @"// not a comment"; }
+@end
@@ -0,0 +1,6 @@
+(* Greets. *)
+let greet name =
+  (* Outer (* inner *) comment *)
+  "Hello, (* not a comment *) " ^ name
+
+let () = print_endline (greet "world") (* trailing *)
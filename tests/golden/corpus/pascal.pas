@@ -0,0 +1,7 @@
+program Hello;
+{ A brace comment }
+(* A paren comment,
+   spanning lines *)
+begin
+  WriteLn('{ not a comment }'); { trailing }
+end.
@@ -0,0 +1,10 @@
+# Greets.
+package Hello;
+use strict;
+
+=begin comment
+Some POD.
+=cut
+
+sub greet { return "Hello, # not a comment $_[0]"; } # trailing
+1;
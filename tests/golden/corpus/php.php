@@ -0,0 +1,7 @@
+<?php
+// Greets.
+# Also a comment.
+function greet($name) {
+    return "Hello, /* not a comment */ $name"; // trailing
+}
+/* A block comment. */ echo greet('world');
@@ -0,0 +1,8 @@
+# Greets.
+<#
+.SYNOPSIS
+    A block comment.
+#>
+function Greet($Name) {
+    Write-Output "Hello, # not a comment $Name" # trailing
+}
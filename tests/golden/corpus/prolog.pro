@@ -0,0 +1,6 @@
+% Greets.
+/* A block
+   comment. */
+greet(Name) :- format("Hello, ~w % not a comment~n", [Name]). % trailing
+
+:- initialization(greet(world)).
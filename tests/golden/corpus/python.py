@@ -0,0 +1,12 @@
+"""Greets."""
+import sys
+
+# A comment.
+def greet(name):
+    '''
+    A docstring.
+    '''
+    return "Hello, # not a comment " + name  # trailing
+
+
+print(greet(sys.argv[1]))
@@ -0,0 +1,4 @@
+# Greets.
+greet <- function(name) {
+  paste("Hello, # not a comment", name) # trailing
+}
@@ -0,0 +1,9 @@
+# Greets.
+def greet(name)
+  "Hello, # not a comment #{name}" # trailing
+end
+
+=begin
+A block comment.
+=end
+puts greet('world')
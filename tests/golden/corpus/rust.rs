@@ -0,0 +1,12 @@
+//! Greets.
+
+/// Returns a greeting.
+fn greet(name: &str) -> String {
+    /* Outer /* inner */ comment */
+    format!("Hello, /* not a comment */ {}", name) // trailing
+}
+
+fn main() {
+    let s = greet("world"); /* a comment */ let n = s.len();
+    println!("{} {}", s, n);
+}
@@ -0,0 +1,6 @@
+// Greets.
+object Hello {
+  /* Outer /* inner */ comment */
+  def main(args: Array[String]): Unit =
+    println("Hello, // not a comment") // trailing
+}
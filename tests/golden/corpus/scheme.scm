@@ -0,0 +1,4 @@
+;; Greets.
+(define (greet name)
+  #| A block comment |#
+  (string-append "Hello, ; not a comment " name)) ; trailing
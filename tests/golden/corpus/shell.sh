@@ -0,0 +1,5 @@
+#!/bin/sh
+# Greets.
+name='# not a comment'
+
+echo "Hello, $name" # trailing
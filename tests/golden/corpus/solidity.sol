@@ -0,0 +1,7 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/* A greeter. */
+contract Greeter {
+    string greeting = "// not a comment"; // trailing
+}
@@ -0,0 +1,6 @@
+-- Greets.
+/* A block
+   comment. */
+SELECT 'Hello, -- not a comment' AS greeting; -- trailing
+
+SELECT 'it''s' /* trailing */;
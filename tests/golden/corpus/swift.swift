@@ -0,0 +1,5 @@
+// Greets.
+/* Outer /* inner */ comment */
+func greet(_ name: String) -> String {
+    return "Hello, // not a comment \(name)" // trailing
+}
@@ -0,0 +1,5 @@
+# Greets.
+proc greet {name} {
+    return "Hello, # not a comment $name" ;# trailing
+}
+puts [greet world]
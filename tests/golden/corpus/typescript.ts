@@ -0,0 +1,5 @@
+// Greets.
+function greet(name: string): string {
+    /* A block comment. */
+    return `Hello, // not a comment ${name}`; // trailing
+}
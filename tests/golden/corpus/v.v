@@ -0,0 +1,6 @@
+// Greets.
+fn main() {
+	/* A block comment. */
+	name := 'world // not a comment'
+	println('Hello, ${name}') // trailing
+}
@@ -0,0 +1,5 @@
+// Greets.
+void main () {
+    /* A block comment. */
+    print ("Hello, /* not a comment */\n"); // trailing
+}
@@ -0,0 +1,6 @@
+// A counter.
+module counter(input clk, output reg [7:0] q);
+  /* Counts up
+     on each rising edge. */
+  always @(posedge clk) q <= q + 1; // trailing
+endmodule
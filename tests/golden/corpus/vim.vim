@@ -0,0 +1,6 @@
+" Greets.
+function! Greet(name)
+  return 'Hello, ' . a:name
+endfunction
+
+echo Greet('world') " trailing
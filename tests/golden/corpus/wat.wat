@@ -0,0 +1,7 @@
+;; Adds two numbers.
+(module
+  (func $add (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add) ;; trailing
+  (export "add;;" (func $add)))
@@ -0,0 +1,6 @@
+// Greets.
+const std = @import("std");
+
+pub fn main() void {
+    std.debug.print("Hello, // not a comment\n", .{}); // trailing
+}
@@ -0,0 +1,48 @@
+// This file is part of rlocc.
+//
+// Copyright (C) 2020 Christos Katsakioris
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Registers user-defined languages, which can only be done once per process; hence a test of
+//! its own.
+
+use rlocc::locc::{register_languages, Config, Language};
+
+#[test]
+fn overridden_language_aliases() {
+    // Overrides the built-in "Javascript", spelled differently and without its extensions.
+    register_languages(vec![Language {
+        name: "JavaScript",
+        extensions: &["mjs"],
+        file_names: &[],
+        file_name_patterns: &[],
+        interpreters: &[],
+        inline_comment_tokens: &["//"],
+        multiline_comment_start_tokens: &["/*"],
+        multiline_comment_end_tokens: &["*/"],
+        nested_comment_tokens: &[],
+        string_tokens: &["\"", "'"],
+        raw_string_tokens: &[],
+    }])
+    .unwrap();
+
+    let mut config = Config {
+        include_langs: vec!["js".to_owned()],
+        ..Default::default()
+    };
+    config.resolve_langs().unwrap();
+    assert_eq!(config.include_langs, vec!["javascript"]);
+    assert!(config.is_lang_included("JavaScript"));
+}